@@ -32,6 +32,9 @@ pub struct ManifestOpts {
 	/// Write output as YAML stream, can be used with --format json/yaml
 	#[clap(long, short = 'y', conflicts_with = "string")]
 	yaml_stream: bool,
+	/// With --yaml-stream, write non-array output as a single document instead of failing
+	#[clap(long, requires = "yaml_stream")]
+	yaml_stream_wrap_single: bool,
 	/// Number of spaces to pad output manifest with.
 	/// `0` for hard tabs, `-1` for single line output
 	///
@@ -80,7 +83,7 @@ impl ManifestOpts {
 			}
 		};
 		if self.yaml_stream {
-			Box::new(YamlStreamFormat::cli(format))
+			Box::new(YamlStreamFormat::cli(format).with_wrap_single(self.yaml_stream_wrap_single))
 		} else {
 			format
 		}
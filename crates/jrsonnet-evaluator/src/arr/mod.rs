@@ -4,7 +4,7 @@ use jrsonnet_gcmodule::{Cc, Trace};
 use jrsonnet_interner::IBytes;
 use jrsonnet_parser::LocExpr;
 
-use crate::{function::FuncVal, gc::TraceBox, tb, Context, Result, Thunk, Val};
+use crate::{function::FuncVal, gc::TraceBox, tb, Context, Result, ResultExt, Thunk, Val};
 
 mod spec;
 pub use spec::{ArrayLike, *};
@@ -52,6 +52,11 @@ impl ArrValue {
 		Self::new(CharArray(chars.collect()))
 	}
 
+	/// Array of tuples, see [`ZippedArray`]
+	pub fn zip(arrays: Vec<Self>) -> Self {
+		Self::new(ZippedArray::new(arrays))
+	}
+
 	#[must_use]
 	pub fn map(self, mapper: FuncVal) -> Self {
 		Self::new(<MappedArray<false>>::new(self, mapper))
@@ -65,9 +70,9 @@ impl ArrValue {
 	pub fn filter(self, filter: impl Fn(&Val) -> Result<bool>) -> Result<Self> {
 		// TODO: ArrValue::Picked(inner, indexes) for large arrays
 		let mut out = Vec::new();
-		for i in self.iter() {
-			let i = i?;
-			if filter(&i)? {
+		for (idx, i) in self.iter().enumerate() {
+			let i = i.with_description(|| format!("elem <{idx}> evaluation"))?;
+			if filter(&i).with_description(|| format!("elem <{idx}> filtering"))? {
 				out.push(i);
 			};
 		}
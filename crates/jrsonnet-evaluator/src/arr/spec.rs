@@ -6,8 +6,8 @@ use jrsonnet_parser::LocExpr;
 
 use super::ArrValue;
 use crate::{
-	error::ErrorKind::InfiniteRecursionDetected, evaluate, function::FuncVal, typed::Typed,
-	val::ThunkValue, Context, Error, ObjValue, Result, Thunk, Val,
+	error::ErrorKind::InfiniteRecursionDetected, evaluate, function::FuncVal, val::ThunkValue,
+	Context, Error, ObjValue, ObjValueBuilder, Result, ResultExt, Thunk, Val,
 };
 
 pub trait ArrayLike: Any + Trace + Debug {
@@ -450,6 +450,7 @@ impl<const WITH_INDEX: bool> MappedArray<WITH_INDEX> {
 		} else {
 			self.mapper.evaluate_simple(&(value,), false)
 		}
+		.with_description(|| format!("elem <{index}> mapping"))
 	}
 }
 impl<const WITH_INDEX: bool> ArrayLike for MappedArray<WITH_INDEX> {
@@ -532,6 +533,49 @@ impl<const WITH_INDEX: bool> ArrayLike for MappedArray<WITH_INDEX> {
 	}
 }
 
+/// Array of tuples of elements with the same index, truncated to the shortest input
+#[derive(Trace, Debug)]
+pub struct ZippedArray {
+	arrays: Vec<ArrValue>,
+	len: usize,
+}
+impl ZippedArray {
+	pub fn new(arrays: Vec<ArrValue>) -> Self {
+		let len = arrays.iter().map(ArrValue::len).min().unwrap_or(0);
+		Self { arrays, len }
+	}
+	fn tuple(&self, index: usize) -> Val {
+		Val::Arr(ArrValue::lazy(
+			self.arrays
+				.iter()
+				.map(|arr| arr.get_lazy(index).expect("index checked"))
+				.collect(),
+		))
+	}
+}
+impl ArrayLike for ZippedArray {
+	fn len(&self) -> usize {
+		self.len
+	}
+
+	fn get(&self, index: usize) -> Result<Option<Val>> {
+		Ok((index < self.len).then(|| self.tuple(index)))
+	}
+
+	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
+		// Tuple elements are still lazy
+		(index < self.len).then(|| Thunk::evaluated(self.tuple(index)))
+	}
+
+	fn get_cheap(&self, _index: usize) -> Option<Val> {
+		None
+	}
+
+	fn is_cheap(&self) -> bool {
+		false
+	}
+}
+
 #[derive(Trace, Debug)]
 pub struct RepeatedArray {
 	data: ArrValue,
@@ -555,21 +599,21 @@ impl ArrayLike for RepeatedArray {
 	}
 
 	fn get(&self, index: usize) -> Result<Option<Val>> {
-		if index > self.total_len {
+		if index >= self.total_len {
 			return Ok(None);
 		}
 		self.data.get(index % self.data.len())
 	}
 
 	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
-		if index > self.total_len {
+		if index >= self.total_len {
 			return None;
 		}
 		self.data.get_lazy(index % self.data.len())
 	}
 
 	fn get_cheap(&self, index: usize) -> Option<Val> {
-		if index > self.total_len {
+		if index >= self.total_len {
 			return None;
 		}
 		self.data.get_cheap(index % self.data.len())
@@ -627,12 +671,15 @@ impl PickObjectKeyValues {
 	pub fn new(obj: ObjValue, keys: Vec<IStr>) -> Self {
 		Self { obj, keys }
 	}
-}
-
-#[derive(Typed)]
-pub struct KeyValue {
-	key: IStr,
-	value: Thunk<Val>,
+	/// `{key, value}` object, value is only evaluated on access
+	fn key_value(&self, key: &IStr) -> Val {
+		let mut out = ObjValueBuilder::with_capacity(2);
+		out.field("key").value(Val::string(key.clone()));
+		out.field("value")
+			.thunk(self.obj.get_lazy_or_bail(key.clone()))
+			.expect("fields are distinct");
+		Val::Obj(out.build())
+	}
 }
 
 impl ArrayLike for PickObjectKeyValues {
@@ -644,26 +691,14 @@ impl ArrayLike for PickObjectKeyValues {
 		let Some(key) = self.keys.get(index) else {
 			return Ok(None);
 		};
-		Ok(Some(
-			KeyValue::into_untyped(KeyValue {
-				key: key.clone(),
-				value: Thunk::evaluated(self.obj.get_or_bail(key.clone())?),
-			})
-			.expect("convertible"),
-		))
+		Ok(Some(self.key_value(key)))
 	}
 
 	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
 		let key = self.keys.get(index)?;
 		// Nothing can fail in the key part, yet value is still
 		// lazy-evaluated
-		Some(Thunk::evaluated(
-			KeyValue::into_untyped(KeyValue {
-				key: key.clone(),
-				value: self.obj.get_lazy_or_bail(key.clone()),
-			})
-			.expect("convertible"),
-		))
+		Some(Thunk::evaluated(self.key_value(key)))
 	}
 
 	fn get_cheap(&self, _index: usize) -> Option<Val> {
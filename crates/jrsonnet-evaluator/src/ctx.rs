@@ -38,6 +38,10 @@ impl Context {
 			.as_ref()
 			.expect("used state from dummy context")
 	}
+	/// Dummy contexts have no state
+	pub(crate) fn try_state(&self) -> Option<&State> {
+		self.0.state.as_ref()
+	}
 
 	pub fn dollar(&self) -> Option<&ObjValue> {
 		self.0.dollar.as_ref()
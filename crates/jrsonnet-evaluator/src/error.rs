@@ -7,16 +7,17 @@ use std::{
 
 use jrsonnet_gcmodule::Trace;
 use jrsonnet_interner::IStr;
-use jrsonnet_parser::{BinaryOpType, LocExpr, Source, SourcePath, Span, UnaryOpType};
+use jrsonnet_parser::{BinaryOpType, CodeLocation, LocExpr, Source, SourcePath, Span, UnaryOpType};
 use jrsonnet_types::ValType;
 use thiserror::Error;
 
 use crate::{
 	function::{builtin::ParamDefault, CallLocation},
 	stdlib::format::FormatError,
+	trace::PathResolver,
 	typed::TypeLocError,
-	val::ConvertNumValueError,
-	ObjValue,
+	val::{ArrValue, ConvertNumValueError},
+	ObjValue, Val,
 };
 
 pub(crate) fn format_found(list: &[IStr], what: &str) -> String {
@@ -216,6 +217,12 @@ pub enum ErrorKind {
 	StackOverflow,
 	#[error("infinite recursion detected")]
 	InfiniteRecursionDetected,
+	#[error("evaluation step limit exceeded")]
+	EvaluationStepLimitExceeded,
+	#[error("evaluation timeout")]
+	EvaluationTimeout,
+	#[error("allocation budget exceeded")]
+	AllocationBudgetExceeded,
 	#[error("tried to index by fractional value")]
 	FractionalIndex,
 	#[error("attempted to divide by zero")]
@@ -301,6 +308,54 @@ impl Error {
 	pub fn trace_mut(&mut self) -> &mut StackTrace {
 		&mut (self.0).1
 	}
+
+	/// Machine-readable representation of this error
+	///
+	/// Produces `{message, frames: [{description, location}]}`, where `location` is either `null`
+	/// or `{file, begin: {offset, line, column}, end: {offset, line, column}}`, with 1-based lines and
+	/// columns, and file paths displayed using `resolver`.
+	/// Result may be manifested with any [`crate::manifest::ManifestFormat`], i.e `JsonFormat`
+	pub fn to_val(&self, resolver: &PathResolver) -> Val {
+		fn code_location(loc: &CodeLocation) -> Val {
+			let num =
+				|v: usize| Val::try_num(v).expect("source positions are in safe integer range");
+			let mut out = ObjValue::builder_with_capacity(3);
+			out.field("offset").value(num(loc.offset));
+			out.field("line").value(num(loc.line));
+			// Stored column is off by one, same as in trace formats
+			out.field("column").value(num(loc.column.saturating_sub(1)));
+			Val::Obj(out.build())
+		}
+
+		let frames = self
+			.trace()
+			.0
+			.iter()
+			.map(|el| {
+				let location = el.location.as_ref().map_or(Val::Null, |span| {
+					let source_path = span.0.source_path();
+					let file = source_path
+						.path()
+						.map_or_else(|| source_path.to_string(), |p| resolver.resolve(p));
+					let [begin, end] = span.0.map_source_locations(&[span.1, span.2]);
+					let mut out = ObjValue::builder_with_capacity(3);
+					out.field("file").value(file);
+					out.field("begin").value(code_location(&begin));
+					out.field("end").value(code_location(&end));
+					Val::Obj(out.build())
+				});
+				let mut out = ObjValue::builder_with_capacity(2);
+				out.field("description").value(el.desc.as_str());
+				out.field("location").value(location);
+				Val::Obj(out.build())
+			})
+			.collect::<Vec<_>>();
+
+		let mut out = ObjValue::builder_with_capacity(2);
+		out.field("message").value(self.error().to_string());
+		out.field("frames").value(Val::Arr(ArrValue::eager(frames)));
+		Val::Obj(out.build())
+	}
 }
 impl Display for Error {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
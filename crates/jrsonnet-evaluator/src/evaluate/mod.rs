@@ -439,6 +439,9 @@ pub fn evaluate(ctx: Context, expr: &LocExpr) -> Result<Val> {
 	if let Some(trivial) = evaluate_trivial(expr) {
 		return Ok(trivial);
 	}
+	if let Some(state) = ctx.try_state() {
+		state.count_evaluation_step()?;
+	}
 	let loc = expr.span();
 	Ok(match expr.expr() {
 		Literal(LiteralType::This) => {
@@ -21,6 +21,8 @@ pub fn evaluate_unary_op(op: UnaryOpType, b: &Val) -> Result<Val> {
 		(Minus, Num(n)) => Val::try_num(-n.get())?,
 		(Not, Bool(v)) => Bool(!v),
 		(BitNot, Num(n)) => Val::try_num(!(n.get() as i64) as f64)?,
+		#[cfg(feature = "exp-bigint")]
+		(BitNot, BigInt(n)) => BigInt(Box::new(!&**n)),
 		(op, o) => bail!(UnaryOperatorDoesNotOperateOnType(op, o.value_type())),
 	})
 }
@@ -174,6 +176,17 @@ pub fn evaluate_binary_op_normal(a: &Val, op: BinaryOpType, b: &Val) -> Result<V
 		(BigInt(a), Mul, BigInt(b)) => BigInt(Box::new(&**a * &**b)),
 		#[cfg(feature = "exp-bigint")]
 		(BigInt(a), Sub, BigInt(b)) => BigInt(Box::new(&**a - &**b)),
+		#[cfg(feature = "exp-bigint")]
+		(BigInt(a), BitAnd, BigInt(b)) => BigInt(Box::new(&**a & &**b)),
+		#[cfg(feature = "exp-bigint")]
+		(BigInt(a), BitOr, BigInt(b)) => BigInt(Box::new(&**a | &**b)),
+		#[cfg(feature = "exp-bigint")]
+		(BigInt(a), BitXor, BigInt(b)) => BigInt(Box::new(&**a ^ &**b)),
+		// Shift exponent may also be a plain number, as it is usually small
+		#[cfg(feature = "exp-bigint")]
+		(BigInt(a), Lhs, b @ (Num(_) | BigInt(_))) => BigInt(Box::new(&**a << bigint_shift(b)?)),
+		#[cfg(feature = "exp-bigint")]
+		(BigInt(a), Rhs, b @ (Num(_) | BigInt(_))) => BigInt(Box::new(&**a >> bigint_shift(b)?)),
 
 		_ => bail!(BinaryOperatorDoesNotOperateOnValues(
 			op,
@@ -182,3 +195,122 @@ pub fn evaluate_binary_op_normal(a: &Val, op: BinaryOpType, b: &Val) -> Result<V
 		)),
 	})
 }
+
+/// Shifting by more bits would allocate megabytes for a single value, which is most likely a bug
+#[cfg(feature = "exp-bigint")]
+const MAX_BIGINT_SHIFT: usize = 1 << 20;
+
+#[cfg(feature = "exp-bigint")]
+fn bigint_shift(exp: &Val) -> Result<usize> {
+	let exp = match exp {
+		Val::Num(n) if n.get() < 0.0 => bail!("shift by negative exponent"),
+		Val::Num(n) if n.get().fract() != 0.0 => {
+			bail!("shift exponent should be an integer, got {}", n.get())
+		}
+		Val::Num(n) if n.get() > MAX_BIGINT_SHIFT as f64 => usize::MAX,
+		Val::Num(n) => n.get() as usize,
+		Val::BigInt(n) if n.sign() == num_bigint::Sign::Minus => {
+			bail!("shift by negative exponent")
+		}
+		Val::BigInt(n) => usize::try_from(&**n).unwrap_or(usize::MAX),
+		_ => unreachable!("checked by caller"),
+	};
+	if exp > MAX_BIGINT_SHIFT {
+		bail!("shift exponent is too large, at most {MAX_BIGINT_SHIFT} is allowed");
+	}
+	Ok(exp)
+}
+
+#[cfg(all(test, feature = "exp-bigint"))]
+mod tests {
+	use super::*;
+
+	fn big(v: &str) -> Val {
+		Val::BigInt(Box::new(v.parse().expect("valid bigint")))
+	}
+
+	fn binary(a: &Val, op: BinaryOpType, b: &Val) -> Val {
+		evaluate_binary_op_normal(a, op, b).expect("operation succeeds")
+	}
+
+	// Expected values are computed using python integers
+	#[test]
+	fn bigint_bitwise() {
+		use BinaryOpType::*;
+		let a = big("295990755076957304699390954001009532656");
+		let neg_a = big("-295990755076957304699390954001009532656");
+		let b = big("5192217631581220737344928932233215");
+
+		for (lhs, op, rhs, expected) in [
+			(&a, BitAnd, &b, "3872593356416591604517666969149168"),
+			(&a, BitOr, &b, "295992074701232469328523781262972616703"),
+			(&a, BitXor, &b, "295988202107876052736919263596003467535"),
+			(&neg_a, BitAnd, &b, "1319624275164629132827261963084048"),
+			(
+				&neg_a,
+				BitOr,
+				&b,
+				"-295986882483600888107786436334040383489",
+			),
+			(
+				&neg_a,
+				BitXor,
+				&b,
+				"-295988202107876052736919263596003467537",
+			),
+		] {
+			assert!(equals(&binary(lhs, op, rhs), &big(expected)).unwrap());
+		}
+	}
+
+	#[test]
+	fn bigint_shift() {
+		use BinaryOpType::*;
+		let a = big("295990755076957304699390954001009532656");
+		let neg_a = big("-295990755076957304699390954001009532656");
+
+		for (lhs, op, rhs, expected) in [
+			(
+				&a,
+				Lhs,
+				Val::try_num(40.0).unwrap(),
+				"325445276921312662239280962189459531197891588653056",
+			),
+			(
+				&a,
+				Lhs,
+				big("40"),
+				"325445276921312662239280962189459531197891588653056",
+			),
+			(
+				&a,
+				Rhs,
+				Val::try_num(7.0).unwrap(),
+				"2312427774038728942963991828132886973",
+			),
+			(
+				&neg_a,
+				Rhs,
+				Val::try_num(7.0).unwrap(),
+				"-2312427774038728942963991828132886974",
+			),
+		] {
+			assert!(equals(&binary(lhs, op, &rhs), &big(expected)).unwrap());
+		}
+		assert!(evaluate_binary_op_normal(&a, Lhs, &big("-1")).is_err());
+		assert!(evaluate_binary_op_normal(&a, Rhs, &Val::try_num(-1.0).unwrap()).is_err());
+		assert!(evaluate_binary_op_normal(&a, Lhs, &Val::try_num(1.5).unwrap()).is_err());
+		assert!(evaluate_binary_op_normal(&a, Lhs, &Val::try_num(1e18).unwrap()).is_err());
+		assert!(evaluate_binary_op_normal(&a, Rhs, &big("100000000000000000000")).is_err());
+	}
+
+	#[test]
+	fn bigint_not() {
+		let a = big("295990755076957304699390954001009532656");
+		assert!(equals(
+			&evaluate_unary_op(UnaryOpType::BitNot, &a).unwrap(),
+			&big("-295990755076957304699390954001009532657")
+		)
+		.unwrap());
+	}
+}
@@ -1,8 +1,11 @@
-use std::borrow::Cow;
+use std::{
+	borrow::Cow,
+	collections::{hash_map::Entry, HashMap},
+};
 
 use jrsonnet_interner::IStr;
 use serde::{
-	de::{self, Visitor},
+	de::{self, DeserializeSeed, Visitor},
 	ser::{
 		Error, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
 		SerializeTupleStruct, SerializeTupleVariant,
@@ -15,137 +18,236 @@ use crate::{
 	ObjValueBuilder, Result, Val,
 };
 
-impl<'de> Deserialize<'de> for Val {
+/// Integers above this value can't be represented as f64 without precision loss,
+/// with bigint support enabled they are deserialized as [`Val::BigInt`]
+#[cfg(feature = "exp-bigint")]
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Object key, which is only allowed to be a string.
+///
+/// Formats with non-string keys (yaml) are free to pass scalar keys in their textual form
+struct MapKey<'de>(Cow<'de, str>);
+impl<'de> Deserialize<'de> for MapKey<'de> {
 	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
 	where
 		D: serde::Deserializer<'de>,
 	{
-		struct ValVisitor;
-
-		// macro_rules! visit_num {
-		// 	($($method:ident => $ty:ty),* $(,)?) => {$(
-		// 		fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
-		// 		where
-		// 			E: serde::de::Error,
-		// 		{
-		// 			Ok(Val::Num(f64::from(v)))
-		// 		}
-		// 	)*};
-		// }
-
-		impl<'de> Visitor<'de> for ValVisitor {
-			type Value = Val;
-
-			fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Bool(v))
+		struct KeyVisitor;
+		impl<'de> Visitor<'de> for KeyVisitor {
+			type Value = MapKey<'de>;
+
+			fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+				Ok(MapKey(Cow::Borrowed(v)))
 			}
-			fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Num(NumValue::new(v).ok_or_else(|| {
-					E::custom("only finite numbers are supported")
-				})?))
+			fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+				Ok(MapKey(Cow::Owned(v.to_owned())))
 			}
-			fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::string(v))
+			fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+				Ok(MapKey(Cow::Owned(v)))
 			}
 
-			// visit_num! {
-			// 	visit_i8 => i8,
-			// 	visit_i16 => i16,
-			// 	visit_i32 => i32,
-			// 	visit_u8 => u8,
-			// 	visit_u16 => u16,
-			// 	visit_u32 => u32,
-			// }
-			fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Num(NumValue::new(v as f64).expect("no overflow")))
-			}
-			fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Num(NumValue::new(v as f64).expect("no overflow")))
+			fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				write!(
+					formatter,
+					"a string object key, arrays and objects can't be used as keys"
+				)
 			}
+		}
+		deserializer.deserialize_str(KeyVisitor)
+	}
+}
 
-			fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Arr(ArrValue::bytes(v.into())))
-			}
+#[derive(Clone, Copy)]
+struct ValVisitor<'p> {
+	/// Set for strict deserialization
+	strict: Option<StrictValSeed<'p>>,
+}
+impl<'de> DeserializeSeed<'de> for ValVisitor<'_> {
+	type Value = Val;
 
-			fn visit_none<E>(self) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Null)
-			}
-			fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-			where
-				D: serde::Deserializer<'de>,
-			{
-				deserializer.deserialize_any(self)
-			}
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		deserializer.deserialize_any(self)
+	}
+}
 
-			fn visit_unit<E>(self) -> Result<Self::Value, E>
-			where
-				E: de::Error,
-			{
-				Ok(Val::Null)
-			}
+// macro_rules! visit_num {
+// 	($($method:ident => $ty:ty),* $(,)?) => {$(
+// 		fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+// 		where
+// 			E: serde::de::Error,
+// 		{
+// 			Ok(Val::Num(f64::from(v)))
+// 		}
+// 	)*};
+// }
+
+impl<'de> Visitor<'de> for ValVisitor<'_> {
+	type Value = Val;
+
+	fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::Bool(v))
+	}
+	fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::Num(NumValue::new(v).ok_or_else(|| {
+			E::custom("only finite numbers are supported")
+		})?))
+	}
+	fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::string(v))
+	}
+
+	// visit_num! {
+	// 	visit_i8 => i8,
+	// 	visit_i16 => i16,
+	// 	visit_i32 => i32,
+	// 	visit_u8 => u8,
+	// 	visit_u16 => u16,
+	// 	visit_u32 => u32,
+	// }
+	fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		#[cfg(feature = "exp-bigint")]
+		if v.unsigned_abs() > MAX_SAFE_INTEGER {
+			return Ok(Val::BigInt(Box::new(v.into())));
+		}
+		Ok(Val::Num(NumValue::new(v as f64).expect("no overflow")))
+	}
+	fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		#[cfg(feature = "exp-bigint")]
+		if v > MAX_SAFE_INTEGER {
+			return Ok(Val::BigInt(Box::new(v.into())));
+		}
+		Ok(Val::Num(NumValue::new(v as f64).expect("no overflow")))
+	}
 
-			fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-			where
-				D: serde::Deserializer<'de>,
-			{
-				deserializer.deserialize_any(self)
-			}
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::Arr(ArrValue::bytes(v.into())))
+	}
 
-			fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-			where
-				A: de::SeqAccess<'de>,
-			{
-				let mut out = seq.size_hint().map_or_else(Vec::new, Vec::with_capacity);
+	fn visit_none<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::Null)
+	}
+	fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		deserializer.deserialize_any(self)
+	}
 
-				while let Some(val) = seq.next_element::<Val>()? {
-					out.push(val);
-				}
+	fn visit_unit<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		Ok(Val::Null)
+	}
 
-				Ok(Val::Arr(ArrValue::eager(out)))
-			}
+	fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		deserializer.deserialize_any(self)
+	}
 
-			fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-			where
-				A: de::MapAccess<'de>,
-			{
-				let mut out = map
-					.size_hint()
-					.map_or_else(ObjValueBuilder::new, ObjValueBuilder::with_capacity);
-
-				while let Some((k, v)) = map.next_entry::<Cow<'de, str>, Val>()? {
-					// Jsonnet ignores duplicate keys
-					out.field(k).value(v);
-				}
+	fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::SeqAccess<'de>,
+	{
+		let mut out = seq.size_hint().map_or_else(Vec::new, Vec::with_capacity);
 
-				Ok(Val::Obj(out.build()))
-			}
+		while let Some(val) = seq.next_element_seed(self)? {
+			out.push(val);
+		}
 
-			fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-				write!(formatter, "any valid jsonnet value")
+		Ok(Val::Arr(ArrValue::eager(out)))
+	}
+
+	fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::MapAccess<'de>,
+	{
+		let mut out = map
+			.size_hint()
+			.map_or_else(ObjValueBuilder::new, ObjValueBuilder::with_capacity);
+
+		let mut seen = HashMap::new();
+		while let Some(MapKey(k)) = map.next_key::<MapKey<'de>>()? {
+			let k = IStr::from(k.as_ref());
+			if let Some(strict) = self.strict {
+				match seen.entry(k.clone()) {
+					Entry::Occupied(first) => {
+						return Err(de::Error::custom(format!(
+							"duplicate key <{k}>, first defined at {}, repeated",
+							(strict.describe_position)(*first.get())
+						)));
+					}
+					Entry::Vacant(v) => {
+						v.insert((strict.position)());
+					}
+				}
 			}
+			// Jsonnet ignores duplicate keys
+			out.field(k).value(map.next_value_seed(self)?);
 		}
-		deserializer.deserialize_any(ValVisitor)
+
+		Ok(Val::Obj(out.build()))
+	}
+
+	fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "any valid jsonnet value")
+	}
+}
+impl<'de> Deserialize<'de> for Val {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		deserializer.deserialize_any(ValVisitor { strict: None })
+	}
+}
+
+/// Deserializes [`Val`], failing on duplicate object keys instead of keeping the last value.
+///
+/// `position` is called after every object key is read, and should cheaply return the current input
+/// position, i.e byte offset. `describe_position` is only called for the first occurrence of the reported
+/// duplicate key, to convert its position to the description used in the error.
+/// Position of the repeated occurrence is expected to be appended by the format itself,
+/// as `serde_json` does
+#[derive(Clone, Copy)]
+pub struct StrictValSeed<'p> {
+	pub position: &'p dyn Fn() -> usize,
+	pub describe_position: &'p dyn Fn(usize) -> String,
+}
+impl<'de> DeserializeSeed<'de> for StrictValSeed<'_> {
+	type Value = Val;
+
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		deserializer.deserialize_any(ValVisitor { strict: Some(self) })
 	}
 }
 
@@ -27,9 +27,10 @@ pub mod val;
 
 use std::{
 	any::Any,
-	cell::{RefCell, RefMut},
+	cell::{Cell, RefCell, RefMut},
 	fmt::{self, Debug},
 	path::Path,
+	time::{Duration, Instant},
 };
 
 pub use ctx::*;
@@ -40,6 +41,7 @@ use function::CallLocation;
 use gc::{GcHashMap, TraceBox};
 use hashbrown::hash_map::RawEntryMut;
 pub use import::*;
+pub use integrations::serde::StrictValSeed;
 use jrsonnet_gcmodule::{Cc, Trace};
 pub use jrsonnet_interner::{IBytes, IStr};
 #[doc(hidden)]
@@ -221,8 +223,27 @@ pub struct EvaluationStateInternals {
 	context_initializer: TraceBox<dyn ContextInitializer>,
 	/// Used to resolve file locations/contents
 	import_resolver: TraceBox<dyn ImportResolver>,
+	/// Number of evaluated expressions, see [`StateBuilder::evaluation_step_limit`]
+	#[trace(skip)]
+	evaluation_steps: Cell<u64>,
+	#[trace(skip)]
+	evaluation_step_limit: Option<u64>,
+	/// See [`StateBuilder::evaluation_timeout`]
+	#[trace(skip)]
+	evaluation_timeout: Option<Duration>,
+	#[trace(skip)]
+	evaluation_started: Cell<Option<Instant>>,
+	/// Number of running top-level evaluations, limits are only reset by the outermost one
+	#[trace(skip)]
+	evaluation_depth: Cell<u32>,
+	/// See [`StateBuilder::allocation_budget`]
+	#[trace(skip)]
+	allocation_budget: Option<usize>,
 }
 
+/// Timeout is only checked once per this number of evaluation steps, as getting current time is not free
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
 /// Maintains stack trace and import resolution
 #[derive(Clone, Trace)]
 pub struct State(Cc<EvaluationStateInternals>);
@@ -281,6 +302,7 @@ impl State {
 	}
 	/// Should only be called with path retrieved from [`resolve_path`], may panic otherwise
 	pub fn import_resolved(&self, path: SourcePath) -> Result<Val> {
+		let _guard = self.enter_evaluation();
 		let mut file_cache = self.file_cache();
 		let mut file = file_cache.raw_entry_mut().from_key(&path);
 
@@ -422,6 +444,7 @@ impl ContextInitializer for InitialUnderscore {
 impl State {
 	/// Parses and evaluates the given snippet
 	pub fn evaluate_snippet(&self, name: impl Into<IStr>, code: impl Into<IStr>) -> Result<Val> {
+		let _guard = self.enter_evaluation();
 		let code = code.into();
 		let source = Source::new_virtual(name.into(), code.clone());
 		let parsed = jrsonnet_parser::parse(
@@ -443,6 +466,7 @@ impl State {
 		code: impl Into<IStr>,
 		context_initializer: impl ContextInitializer,
 	) -> Result<Val> {
+		let _guard = self.enter_evaluation();
 		let code = code.into();
 		let source = Source::new_virtual(name.into(), code.clone());
 		let parsed = jrsonnet_parser::parse(
@@ -481,6 +505,75 @@ impl State {
 	pub fn context_initializer(&self) -> &dyn ContextInitializer {
 		&*self.0.context_initializer
 	}
+
+	/// Fails if value of the specified size (number of array elements, or string length in bytes)
+	/// is larger than allowed by [`StateBuilder::allocation_budget`]
+	pub fn check_allocation(&self, size: usize) -> Result<()> {
+		if self.0.allocation_budget.is_some_and(|budget| size > budget) {
+			bail!(AllocationBudgetExceeded);
+		}
+		Ok(())
+	}
+
+	/// Number of evaluation steps performed since the last reset of evaluation limits,
+	/// see [`Self::reset_evaluation_limits`]
+	pub fn evaluation_steps(&self) -> u64 {
+		self.0.evaluation_steps.get()
+	}
+	/// Resets the step counter, and restarts the clock of [`StateBuilder::evaluation_timeout`].
+	///
+	/// Limits are reset automatically by every top-level `evaluate_snippet*` and `import*` call
+	/// (but not by imports done during evaluation). Values returned by these calls are lazy,
+	/// so their manifestification and TLA application is counted towards the same evaluation,
+	/// call this method first if they are done as a separate job, i.e long after the evaluation.
+	pub fn reset_evaluation_limits(&self) {
+		self.0.evaluation_steps.set(0);
+		self.0
+			.evaluation_started
+			.set(self.0.evaluation_timeout.map(|_| Instant::now()));
+	}
+	fn enter_evaluation(&self) -> EvaluationGuard<'_> {
+		let depth = self.0.evaluation_depth.get();
+		if depth == 0 {
+			self.reset_evaluation_limits();
+		}
+		self.0.evaluation_depth.set(depth + 1);
+		EvaluationGuard(self)
+	}
+	/// Counts evaluation step, failing if the configured step limit is reached, or the evaluation has timed out
+	pub(crate) fn count_evaluation_step(&self) -> Result<()> {
+		let steps = self.0.evaluation_steps.get() + 1;
+		if self
+			.0
+			.evaluation_step_limit
+			.is_some_and(|limit| steps > limit)
+		{
+			bail!(EvaluationStepLimitExceeded);
+		}
+		if let Some(timeout) = self.0.evaluation_timeout {
+			// Timer is started by the top-level evaluation, or, if the value is evaluated outside of it,
+			// by the first step
+			if steps % TIMEOUT_CHECK_INTERVAL == 1 {
+				let now = Instant::now();
+				let started = self.0.evaluation_started.get().unwrap_or(now);
+				self.0.evaluation_started.set(Some(started));
+				if now.duration_since(started) > timeout {
+					bail!(EvaluationTimeout);
+				}
+			}
+		}
+		self.0.evaluation_steps.set(steps);
+		Ok(())
+	}
+}
+
+/// Marks running top-level evaluation, see [`State::reset_evaluation_limits`]
+struct EvaluationGuard<'s>(&'s State);
+impl Drop for EvaluationGuard<'_> {
+	fn drop(&mut self) {
+		let depth = &self.0 .0.evaluation_depth;
+		depth.set(depth.get() - 1);
+	}
 }
 
 impl State {
@@ -499,6 +592,9 @@ impl Default for State {
 pub struct StateBuilder {
 	import_resolver: Option<TraceBox<dyn ImportResolver>>,
 	context_initializer: Option<TraceBox<dyn ContextInitializer>>,
+	evaluation_step_limit: Option<u64>,
+	evaluation_timeout: Option<Duration>,
+	allocation_budget: Option<usize>,
 }
 impl StateBuilder {
 	pub fn import_resolver(&mut self, import_resolver: impl ImportResolver) -> &mut Self {
@@ -512,6 +608,36 @@ impl StateBuilder {
 		let _ = self.context_initializer.insert(tb!(context_initializer));
 		self
 	}
+	/// Limits total number of evaluated expressions (which includes thunk evaluations and function calls)
+	/// per top-level evaluation (see [`State::reset_evaluation_limits`]), evaluation fails with
+	/// [`EvaluationStepLimitExceeded`] once the limit is reached.
+	///
+	/// Unlike timeouts, this limit is deterministic, and is useful to bound work done by untrusted code
+	pub fn evaluation_step_limit(&mut self, limit: u64) -> &mut Self {
+		self.evaluation_step_limit = Some(limit);
+		self
+	}
+	/// Limits wall-clock time spent on evaluation, timer is started by every top-level evaluation
+	/// (see [`State::reset_evaluation_limits`]), and evaluation fails with [`EvaluationTimeout`]
+	/// once it runs out.
+	///
+	/// Time is only checked once per 1024 evaluation steps (see [`Self::evaluation_step_limit`]),
+	/// and long-running builtins (i.e sorting of a large array) are not interrupted,
+	/// so the actual evaluation time may exceed the timeout.
+	/// When no timeout is set, the only overhead is the step counter
+	pub fn evaluation_timeout(&mut self, timeout: Duration) -> &mut Self {
+		self.evaluation_timeout = Some(timeout);
+		self
+	}
+	/// Limits size of a single array (in elements) or string (in bytes) produced by builtins which
+	/// may create large values from small inputs, such as `std.repeat`, `std.makeArray` and `std.join`.
+	///
+	/// Exceeding the budget fails with [`AllocationBudgetExceeded`] before anything is allocated.
+	/// There is no budget by default
+	pub fn allocation_budget(&mut self, budget: usize) -> &mut Self {
+		self.allocation_budget = Some(budget);
+		self
+	}
 	pub fn build(mut self) -> State {
 		State(Cc::new(EvaluationStateInternals {
 			file_cache: RefCell::new(GcHashMap::new()),
@@ -520,6 +646,12 @@ impl StateBuilder {
 				.import_resolver
 				.take()
 				.unwrap_or_else(|| tb!(DummyImportResolver)),
+			evaluation_steps: Cell::new(0),
+			evaluation_step_limit: self.evaluation_step_limit,
+			evaluation_timeout: self.evaluation_timeout,
+			evaluation_started: Cell::new(None),
+			evaluation_depth: Cell::new(0),
+			allocation_budget: self.allocation_budget,
 		}))
 	}
 }
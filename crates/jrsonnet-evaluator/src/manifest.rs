@@ -1,9 +1,25 @@
-use std::{borrow::Cow, fmt::Write, ptr};
+use std::{
+	borrow::Cow, collections::hash_map::Entry, convert::Infallible, fmt::Write, io, ptr, rc::Rc,
+};
 
-use crate::{bail, in_description_frame, Result, ResultExt, Val};
+use jrsonnet_interner::IStr;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+	bail, in_description_frame, stack::check_depth, val::ArrValue, ObjValue, Result, ResultExt, Val,
+};
 
 pub trait ManifestFormat {
 	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()>;
+	/// Same as [`Self::manifest_buf`], but fails once the output exceeds `budget`.
+	///
+	/// Default implementation checks the size after the whole value is manifested, formats writing
+	/// the output incrementally should check the budget after every written value instead, so
+	/// oversized output is never fully built.
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		self.manifest_buf(val, buf)?;
+		budget.check(buf)
+	}
 	fn manifest(&self, val: Val) -> Result<String> {
 		let mut out = String::new();
 		self.manifest_buf(val, &mut out)?;
@@ -25,6 +41,10 @@ where
 		let inner = &**self;
 		inner.manifest_buf(val, buf)
 	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		let inner = &**self;
+		inner.manifest_buf_limited(val, buf, budget)
+	}
 	fn file_trailing_newline(&self) -> bool {
 		let inner = &**self;
 		inner.file_trailing_newline()
@@ -38,12 +58,62 @@ where
 		let inner = &**self;
 		inner.manifest_buf(val, buf)
 	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		let inner = &**self;
+		inner.manifest_buf_limited(val, buf, budget)
+	}
 	fn file_trailing_newline(&self) -> bool {
 		let inner = &**self;
 		inner.file_trailing_newline()
 	}
 }
 
+/// Maximum number of bytes, which may be written to the output buffer, see [`SizeLimited`]
+#[derive(Clone, Copy, Debug)]
+pub struct SizeBudget {
+	/// Length of the buffer before manifestification
+	start: usize,
+	limit: usize,
+}
+impl SizeBudget {
+	/// Budget for the output appended to `buf` after this call
+	pub fn new(buf: &str, limit: usize) -> Self {
+		Self {
+			start: buf.len(),
+			limit,
+		}
+	}
+	/// Fails if more than `limit` bytes were appended to `buf`
+	pub fn check(&self, buf: &str) -> Result<()> {
+		if buf.len() - self.start > self.limit {
+			bail!("manifest size limit of {} bytes exceeded", self.limit);
+		}
+		Ok(())
+	}
+	/// The budget which is exceeded first
+	#[must_use]
+	pub fn min(self, other: Self) -> Self {
+		if self.start + self.limit <= other.start + other.limit {
+			self
+		} else {
+			other
+		}
+	}
+}
+
+/// Calls [`ManifestFormat::manifest_buf_limited`] if there is a budget
+fn manifest_inner<I: ManifestFormat + ?Sized>(
+	inner: &I,
+	val: Val,
+	buf: &mut String,
+	budget: Option<SizeBudget>,
+) -> Result<()> {
+	match budget {
+		Some(budget) => inner.manifest_buf_limited(val, buf, budget),
+		None => inner.manifest_buf(val, buf),
+	}
+}
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum JsonFormatting {
 	// Applied in manifestification
@@ -57,6 +127,22 @@ enum JsonFormatting {
 	Minify,
 }
 
+/// What to do when [`JsonFormat::with_key_rename`] callback returns the same key for multiple fields
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenameCollision {
+	/// Fail, naming both original keys
+	#[default]
+	Error,
+	/// Value of the last colliding field is used, at the position of the first one
+	LastWins,
+	/// Value of the first colliding field is used, others are skipped
+	First,
+}
+
+/// Callback used in [`JsonFormat::with_key_rename`]
+pub type KeyRename<'s> = Rc<dyn Fn(&IStr) -> Result<IStr> + 's>;
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct JsonFormat<'s> {
 	padding: Cow<'s, str>,
 	mtype: JsonFormatting,
@@ -67,6 +153,18 @@ pub struct JsonFormat<'s> {
 	#[cfg(feature = "exp-bigint")]
 	preserve_bigints: bool,
 	debug_truncate_strings: Option<usize>,
+	/// Character used to quote strings and keys, `"` for json
+	quote: u8,
+	/// Emit integers outside of JS safe integer range as strings
+	quote_unsafe_integers: bool,
+	/// Write every array element on its own line, with nested objects/arrays minified
+	elements_one_per_line: bool,
+	rename_key: Option<KeyRename<'s>>,
+	on_rename_collision: RenameCollision,
+	/// Maximum nesting of arrays/objects, unlimited if `None`
+	max_depth: Option<usize>,
+	/// Escape `/` as `\/`
+	escape_forward_slash: bool,
 }
 
 impl<'s> JsonFormat<'s> {
@@ -82,6 +180,13 @@ impl<'s> JsonFormat<'s> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: false,
 			debug_truncate_strings: None,
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
 		}
 	}
 	/// Same format as std.toString, except does not keeps top-level string as-is
@@ -97,6 +202,13 @@ impl<'s> JsonFormat<'s> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: false,
 			debug_truncate_strings: None,
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
 		}
 	}
 	pub fn std_to_json(
@@ -115,6 +227,13 @@ impl<'s> JsonFormat<'s> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: false,
 			debug_truncate_strings: None,
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
 		}
 	}
 	// Same format as CLI manifestification
@@ -138,6 +257,13 @@ impl<'s> JsonFormat<'s> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: false,
 			debug_truncate_strings: None,
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
 		}
 	}
 	// Same format as CLI manifestification
@@ -152,7 +278,123 @@ impl<'s> JsonFormat<'s> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: true,
 			debug_truncate_strings: Some(256),
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
+		}
+	}
+	/// Use another character for string quoting, i.e `'` for JS/Python-like output.
+	/// Occurences of this character in strings are escaped, `"` is not escaped unless it is used as a quote.
+	///
+	/// Fails if quote is neither `"` nor `'`, other characters might be mistaken for escapes
+	/// (i.e `n` would produce `n\nn` for a newline) or wouldn't be understood as quotes by parsers.
+	pub fn with_quote(mut self, quote: char) -> Result<Self> {
+		if !matches!(quote, '"' | '\'') {
+			bail!("quote should be either '\"' or '\\'', got {quote:?}");
+		}
+		self.quote = quote as u8;
+		Ok(self)
+	}
+	/// Emit integer numbers which can't be precisely represented by JS `Number`
+	/// (i.e greater than `Number.MAX_SAFE_INTEGER`) as quoted strings, so they won't be silently rounded
+	/// by the consumer.
+	///
+	/// With `exp-bigint`, this also applies to bigints, which are otherwise emitted as numbers when preserved.
+	#[must_use]
+	pub fn with_quote_unsafe_integers(mut self, quote_unsafe_integers: bool) -> Self {
+		self.quote_unsafe_integers = quote_unsafe_integers;
+		self
+	}
+	/// Hybrid between pretty and minified output: every array element is written on its own line,
+	/// but element objects/arrays are minified, i.e
+	/// ```json
+	/// [
+	///     {"a":1,"b":[1,2]},
+	///     {"a":2,"b":[]}
+	/// ]
+	/// ```
+	///
+	/// Objects are still pretty-printed until the first array is reached, everything nested in array
+	/// elements is minified.
+	/// Has no effect on minified and `std.toString` formats.
+	#[must_use]
+	pub fn with_elements_one_per_line(mut self, elements_one_per_line: bool) -> Self {
+		self.elements_one_per_line = elements_one_per_line;
+		self
+	}
+
+	/// Rename object keys on output, i.e to convert them to another case.
+	///
+	/// Callback is called with the original field name, the field order is not affected by renames.
+	/// When multiple fields of the same object are renamed to the same key, the behavior is
+	/// controlled by [`Self::with_rename_collision`].
+	#[must_use]
+	pub fn with_key_rename(mut self, rename: impl Fn(&IStr) -> Result<IStr> + 's) -> Self {
+		self.rename_key = Some(Rc::new(rename));
+		self
+	}
+	/// What to do when [key renaming](Self::with_key_rename) produces the same key for multiple fields,
+	/// [`RenameCollision::Error`] by default
+	#[must_use]
+	pub fn with_rename_collision(mut self, on_rename_collision: RenameCollision) -> Self {
+		self.on_rename_collision = on_rename_collision;
+		self
+	}
+
+	/// Fail with an error instead of manifesting arrays/objects nested deeper than `max_depth`,
+	/// i.e with `max_depth = 1`, `[1]` is manifested, but `[[1]]` is not.
+	///
+	/// Protects from deeply nested inputs, which would otherwise be manifested until the stack limit is reached.
+	#[must_use]
+	pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = Some(max_depth);
+		self
+	}
+
+	/// Escape `/` in strings and keys as `\/`, so the output can be safely embedded in HTML `<script>` tag,
+	/// as `</script>` is written as `<\/script>`.
+	#[must_use]
+	pub fn with_escape_forward_slash(mut self, escape_forward_slash: bool) -> Self {
+		self.escape_forward_slash = escape_forward_slash;
+		self
+	}
+
+	fn escape_string(&self, value: &str, buf: &mut String) {
+		// Safety: escaping only writes correct utf-8, quote is ascii
+		let buf: &mut Vec<u8> = unsafe { &mut *ptr::from_mut(buf).cast::<Vec<u8>>() };
+		escape_string_json_sink(value, self.quote, self.escape_forward_slash, buf)
+			.unwrap_or_else(|e| match e {});
+	}
+
+	/// Format for array elements, if it differs from `self`
+	fn element_format(&self) -> Option<JsonFormat<'_>> {
+		if !self.elements_one_per_line
+			|| !matches!(self.mtype, JsonFormatting::Manifest | JsonFormatting::Std)
+		{
+			return None;
 		}
+		Some(JsonFormat {
+			padding: Cow::Borrowed(""),
+			mtype: JsonFormatting::Minify,
+			newline: self.newline,
+			key_val_sep: ":",
+			#[cfg(feature = "exp-preserve-order")]
+			preserve_order: self.preserve_order,
+			#[cfg(feature = "exp-bigint")]
+			preserve_bigints: self.preserve_bigints,
+			debug_truncate_strings: self.debug_truncate_strings,
+			quote: self.quote,
+			quote_unsafe_integers: self.quote_unsafe_integers,
+			elements_one_per_line: false,
+			rename_key: self.rename_key.clone(),
+			on_rename_collision: self.on_rename_collision,
+			max_depth: self.max_depth,
+			escape_forward_slash: self.escape_forward_slash,
+		})
 	}
 }
 impl Default for JsonFormat<'static> {
@@ -167,181 +409,466 @@ impl Default for JsonFormat<'static> {
 			#[cfg(feature = "exp-bigint")]
 			preserve_bigints: false,
 			debug_truncate_strings: None,
+			quote: b'"',
+			quote_unsafe_integers: false,
+			elements_one_per_line: false,
+			rename_key: None,
+			on_rename_collision: RenameCollision::Error,
+			max_depth: None,
+			escape_forward_slash: false,
 		}
 	}
 }
 
 pub fn manifest_json_ex(val: &Val, options: &JsonFormat<'_>) -> Result<String> {
 	let mut out = String::new();
-	manifest_json_ex_buf(val, &mut out, &mut String::new(), options)?;
+	manifest_json_ex_buf(val, &mut out, &mut String::new(), 0, options, None)?;
 	Ok(out)
 }
 
-#[allow(clippy::too_many_lines)]
-fn manifest_json_ex_buf(
-	val: &Val,
+/// Separator between array elements/object fields, including the padding of the new line
+fn write_item_separator(i: usize, buf: &mut String, cur_padding: &str, options: &JsonFormat<'_>) {
+	use JsonFormatting::*;
+	if i != 0 {
+		buf.push(',');
+	}
+	match options.mtype {
+		Manifest | Std => {
+			buf.push_str(options.newline);
+			buf.push_str(cur_padding);
+		}
+		ToString if i != 0 => buf.push(' '),
+		Minify | ToString => {}
+	}
+}
+
+/// Everything between the last array element/object field and the closing bracket
+fn write_container_end(
+	had_items: bool,
 	buf: &mut String,
-	cur_padding: &mut String,
+	cur_padding: &str,
 	options: &JsonFormat<'_>,
-) -> Result<()> {
+) {
 	use JsonFormatting::*;
+	match options.mtype {
+		Manifest | ToString if !had_items => {
+			// Empty array/object as "[ ]"/"{ }"
+			buf.push(' ');
+		}
+		Manifest => {
+			buf.push_str(options.newline);
+			buf.push_str(cur_padding);
+		}
+		Std => {
+			if !had_items {
+				// Stdlib formats empty array/object as "[\n\n]"/"{\n\n}"
+				buf.push_str(options.newline);
+			}
+			buf.push_str(options.newline);
+			buf.push_str(cur_padding);
+		}
+		Minify | ToString => {}
+	}
+}
+
+/// Object fields in manifestification order, as `(output key, field name)` pairs
+fn manifested_fields(obj: &ObjValue, options: &JsonFormat<'_>) -> Result<Vec<(IStr, IStr)>> {
+	let fields = obj.fields(
+		#[cfg(feature = "exp-preserve-order")]
+		options.preserve_order,
+	);
+	let Some(rename) = &options.rename_key else {
+		return Ok(fields.into_iter().map(|f| (f.clone(), f)).collect());
+	};
+	let mut out: Vec<(IStr, IStr)> = Vec::with_capacity(fields.len());
+	let mut positions = FxHashMap::default();
+	for field in fields {
+		let key = in_description_frame(|| format!("field <{field}> rename"), || rename(&field))?;
+		match positions.entry(key.clone()) {
+			Entry::Vacant(e) => {
+				e.insert(out.len());
+				out.push((key, field));
+			}
+			Entry::Occupied(e) => match options.on_rename_collision {
+				RenameCollision::Error => {
+					let first = &out[*e.get()].1;
+					bail!("fields <{first}> and <{field}> are both renamed to <{key}>")
+				}
+				RenameCollision::LastWins => out[*e.get()].1 = field,
+				RenameCollision::First => {}
+			},
+		}
+	}
+	Ok(out)
+}
+
+/// `Number.MAX_SAFE_INTEGER` in JS, `2^53 - 1`
+const JS_MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+fn check_depth_limit(depth: usize, options: &JsonFormat<'_>) -> Result<()> {
+	if options.max_depth.is_some_and(|max| depth >= max) {
+		bail!("manifest depth limit exceeded");
+	}
+	Ok(())
+}
+
+/// Array or object, which is being manifested by [`manifest_json_ex_buf`]
+struct ManifestFrame {
+	container: ManifestContainer,
+	/// Index of the next array element/object field
+	index: usize,
+	/// Length of `cur_padding` before this container was opened
+	old_len: usize,
+	/// Whether this container is an array element, nested in the array with [`JsonFormat::element_format`]
+	element: bool,
+}
+enum ManifestContainer {
+	Arr(ArrValue),
+	Obj(ObjValue, Vec<(IStr, IStr)>),
+}
+impl ManifestFrame {
+	/// Writes the separator and the key of the next child, and returns its value
+	fn next_child(
+		&mut self,
+		buf: &mut String,
+		cur_padding: &str,
+		options: &JsonFormat<'_>,
+	) -> Result<Option<Val>> {
+		let i = self.index;
+		let value = match &self.container {
+			ManifestContainer::Arr(items) => {
+				if i == items.len() {
+					return Ok(None);
+				}
+				let item = items
+					.get(i)
+					.transpose()
+					.expect("index is in bounds")
+					.with_description(|| format!("elem <{i}> evaluation"))?;
+				write_item_separator(i, buf, cur_padding, options);
+				item
+			}
+			ManifestContainer::Obj(obj, fields) => {
+				let Some((key, field)) = fields.get(i) else {
+					return Ok(None);
+				};
+				let value = obj
+					.get(field.clone())
+					.transpose()
+					.expect("field exists")
+					.with_description(|| format!("field <{field}> evaluation"))?;
+				write_item_separator(i, buf, cur_padding, options);
+				options.escape_string(key, buf);
+				buf.push_str(options.key_val_sep);
+				value
+			}
+		};
+		self.index += 1;
+		Ok(Some(value))
+	}
+	/// Description of the child returned by the last [`Self::next_child`] call
+	fn child_description(&self) -> String {
+		let i = self.index - 1;
+		match &self.container {
+			ManifestContainer::Arr(_) => format!("elem <{i}> manifestification"),
+			ManifestContainer::Obj(_, fields) => {
+				format!("field <{}> manifestification", fields[i].1)
+			}
+		}
+	}
+	fn close(self, buf: &mut String, cur_padding: &mut String, options: &JsonFormat<'_>) {
+		cur_padding.truncate(self.old_len);
+		write_container_end(self.index != 0, buf, cur_padding, options);
+		buf.push(match self.container {
+			ManifestContainer::Arr(_) => ']',
+			ManifestContainer::Obj(..) => '}',
+		});
+	}
+}
 
-	let mtype = options.mtype;
-	match val {
+/// Adds descriptions of the values being manifested in `frames` to the error, innermost first,
+/// the same way as the recursive manifestification would.
+fn describe_frames<T>(mut result: Result<T>, frames: &[ManifestFrame]) -> Result<T> {
+	for frame in frames.iter().rev() {
+		result = result.with_description(|| frame.child_description());
+	}
+	result
+}
+
+/// Writes scalar value, or opens the array/object, returning the frame for its children
+fn manifest_value_start(
+	val: Val,
+	buf: &mut String,
+	cur_padding: &mut String,
+	depth: usize,
+	element: bool,
+	options: &JsonFormat<'_>,
+) -> Result<Option<ManifestFrame>> {
+	let container = match val {
 		Val::Bool(v) => {
-			if *v {
+			if v {
 				buf.push_str("true");
 			} else {
 				buf.push_str("false");
 			}
+			return Ok(None);
+		}
+		Val::Null => {
+			buf.push_str("null");
+			return Ok(None);
 		}
-		Val::Null => buf.push_str("null"),
 		Val::Str(s) => {
-			let flat = s.clone().into_flat();
+			let flat = s.into_flat();
 			if let Some(truncate) = options.debug_truncate_strings {
 				if flat.len() > truncate {
 					let (start, end) = flat.split_at(truncate / 2);
 					let (_, end) = end.split_at(end.len() - truncate / 2);
-					escape_string_json_buf(&format!("{start}..{end}"), buf);
+					options.escape_string(&format!("{start}..{end}"), buf);
 				} else {
-					escape_string_json_buf(&flat, buf);
+					options.escape_string(&flat, buf);
 				}
 			} else {
-				escape_string_json_buf(&flat, buf);
+				options.escape_string(&flat, buf);
+			}
+			return Ok(None);
+		}
+		// `NumValue` is always finite, operations producing NaN/Infinity fail where the value is created,
+		// so there is nothing to do about non-finite numbers here
+		Val::Num(n) => {
+			if options.quote_unsafe_integers && n.abs() > JS_MAX_SAFE_INTEGER && n.fract() == 0.0 {
+				let quote = options.quote as char;
+				write!(buf, "{quote}{n}{quote}").unwrap();
+			} else {
+				write!(buf, "{n}").unwrap();
 			}
+			return Ok(None);
 		}
-		Val::Num(n) => write!(buf, "{n}").unwrap(),
 		#[cfg(feature = "exp-bigint")]
 		Val::BigInt(n) => {
-			if options.preserve_bigints {
+			let unsafe_integer = || {
+				use num_bigint::BigInt;
+				let max = BigInt::from(JS_MAX_SAFE_INTEGER as i64);
+				*n > max || *n < -max
+			};
+			if options.preserve_bigints && !(options.quote_unsafe_integers && unsafe_integer()) {
 				write!(buf, "{n}").unwrap();
 			} else {
-				write!(buf, "{:?}", n.to_string()).unwrap();
+				let quote = options.quote as char;
+				write!(buf, "{quote}{n}{quote}").unwrap();
 			}
+			return Ok(None);
 		}
 		Val::Arr(items) => {
+			check_depth_limit(depth, options)?;
 			buf.push('[');
+			ManifestContainer::Arr(items)
+		}
+		Val::Obj(obj) => {
+			check_depth_limit(depth, options)?;
+			obj.run_assertions()?;
+			buf.push('{');
+			let fields = manifested_fields(&obj, options)?;
+			ManifestContainer::Obj(obj, fields)
+		}
+		Val::Func(_) => bail!("tried to manifest function"),
+	};
+	let old_len = cur_padding.len();
+	cur_padding.push_str(&options.padding);
+	Ok(Some(ManifestFrame {
+		container,
+		index: 0,
+		old_len,
+		element,
+	}))
+}
 
-			let old_len = cur_padding.len();
-			cur_padding.push_str(&options.padding);
-
-			let mut had_items = false;
-			for (i, item) in items.iter().enumerate() {
-				had_items = true;
-				let item = item.with_description(|| format!("elem <{i}> evaluation"))?;
+/// `depth` is the number of arrays/objects `val` is nested in
+///
+/// Nested values are manifested using an explicit stack instead of recursion, so deeply nested
+/// values don't overflow the native stack.
+///
+/// `budget` is checked after every written value.
+fn manifest_json_ex_buf(
+	val: &Val,
+	buf: &mut String,
+	cur_padding: &mut String,
+	depth: usize,
+	options: &JsonFormat<'_>,
+	budget: Option<SizeBudget>,
+) -> Result<()> {
+	let check_budget = |buf: &String| budget.map_or(Ok(()), |budget| budget.check(buf));
 
-				if i != 0 {
-					buf.push(',');
-				}
-				match mtype {
-					Manifest | Std => {
-						buf.push_str(options.newline);
-						buf.push_str(cur_padding);
-					}
-					ToString if i != 0 => buf.push(' '),
-					Minify | ToString => {}
-				};
+	let element_format = options.element_format();
+	let element_options = element_format.as_ref().unwrap_or(options);
+	let format_for = |element: bool| if element { element_options } else { options };
 
-				in_description_frame(
-					|| format!("elem <{i}> manifestification"),
-					|| manifest_json_ex_buf(&item, buf, cur_padding, options),
-				)?;
+	let mut stack: Vec<ManifestFrame> = Vec::new();
+	let mut next = Some((val.clone(), false));
+	loop {
+		if let Some((val, element)) = next.take() {
+			let opened = manifest_value_start(
+				val,
+				buf,
+				cur_padding,
+				depth + stack.len(),
+				element,
+				format_for(element),
+			)
+			.and_then(|frame| {
+				check_budget(buf)?;
+				Ok(frame)
+			});
+			match opened {
+				Ok(Some(frame)) => stack.push(frame),
+				Ok(None) => {}
+				Err(e) => return describe_frames(Err(e), &stack),
 			}
-
-			cur_padding.truncate(old_len);
-
-			match mtype {
-				Manifest | ToString if !had_items => {
-					// Empty array as "[ ]"
-					buf.push(' ');
-				}
-				Manifest => {
-					buf.push_str(options.newline);
-					buf.push_str(cur_padding);
-				}
-				Std => {
-					if !had_items {
-						// Stdlib formats empty array as "[\n\n]"
-						buf.push_str(options.newline);
-					}
-					buf.push_str(options.newline);
-					buf.push_str(cur_padding);
-				}
-				Minify | ToString => {}
+		}
+		let Some(frame) = stack.last_mut() else {
+			// Closing brackets were written
+			return check_budget(buf);
+		};
+		let options = format_for(frame.element);
+		match frame.next_child(buf, cur_padding, options) {
+			Ok(Some(child)) => {
+				// Elements of arrays are formatted with element format, and it stays the same for
+				// everything nested in them
+				let element = frame.element || matches!(frame.container, ManifestContainer::Arr(_));
+				next = Some((child, element));
 			}
-
-			buf.push(']');
+			Ok(None) => {
+				let frame = stack.pop().expect("frame exists");
+				frame.close(buf, cur_padding, options);
+			}
+			Err(e) => return describe_frames(Err(e), &stack[..stack.len() - 1]),
 		}
-		Val::Obj(obj) => {
-			obj.run_assertions()?;
-			buf.push('{');
+	}
+}
+
+impl ManifestFormat for JsonFormat<'_> {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		manifest_json_ex_buf(&val, buf, &mut String::new(), 0, self, None)
+	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		manifest_json_ex_buf(&val, buf, &mut String::new(), 0, self, Some(budget))
+	}
+}
 
-			let old_len = cur_padding.len();
-			cur_padding.push_str(&options.padding);
+impl<'s> JsonFormat<'s> {
+	/// Manifest value in chunks, one per top-level array element/object field
+	///
+	/// Concatenation of all chunks is equal to the [`ManifestFormat::manifest`] output.
+	pub fn manifest_chunks(&self, val: Val) -> JsonChunks<'_, 's> {
+		JsonChunks {
+			val,
+			fields: None,
+			options: self,
+			index: 0,
+			finished: false,
+		}
+	}
+}
 
-			let mut had_fields = false;
-			for (i, (key, value)) in obj
-				.iter(
-					#[cfg(feature = "exp-preserve-order")]
-					options.preserve_order,
-				)
-				.enumerate()
-			{
-				had_fields = true;
-				let value = value.with_description(|| format!("field <{key}> evaluation"))?;
-
-				if i != 0 {
-					buf.push(',');
-				}
-				match mtype {
-					Manifest | Std => {
-						buf.push_str(options.newline);
-						buf.push_str(cur_padding);
-					}
-					ToString if i != 0 => buf.push(' '),
-					Minify | ToString => {}
+/// Iterator returned by [`JsonFormat::manifest_chunks`]
+///
+/// Evaluation error is returned in place of the chunk that caused it, after which
+/// the iterator is fused, so the consumer can stop without manifesting the rest of the value.
+pub struct JsonChunks<'f, 's> {
+	val: Val,
+	/// Object fields, collected on the first chunk
+	fields: Option<Vec<(IStr, IStr)>>,
+	options: &'f JsonFormat<'s>,
+	index: usize,
+	finished: bool,
+}
+impl JsonChunks<'_, '_> {
+	fn next_chunk(&mut self) -> Result<String> {
+		let options = self.options;
+		let mut buf = String::new();
+		let mut cur_padding = options.padding.to_string();
+		let i = self.index;
+		self.index += 1;
+		match &self.val {
+			Val::Arr(arr) if !arr.is_empty() => {
+				if i == 0 {
+					check_depth_limit(0, options)?;
+					buf.push('[');
 				}
-
-				escape_string_json_buf(&key, buf);
-				buf.push_str(options.key_val_sep);
+				let item = arr
+					.get(i)
+					.transpose()
+					.expect("index is in bounds")
+					.with_description(|| format!("elem <{i}> evaluation"))?;
+				let element_format = options.element_format();
+				write_item_separator(i, &mut buf, &cur_padding, options);
 				in_description_frame(
-					|| format!("field <{key}> manifestification"),
-					|| manifest_json_ex_buf(&value, buf, cur_padding, options),
+					|| format!("elem <{i}> manifestification"),
+					|| {
+						manifest_json_ex_buf(
+							&item,
+							&mut buf,
+							&mut cur_padding,
+							1,
+							element_format.as_ref().unwrap_or(options),
+							None,
+						)
+					},
 				)?;
+				if self.index == arr.len() {
+					self.finished = true;
+					write_container_end(true, &mut buf, "", options);
+					buf.push(']');
+				}
 			}
-
-			cur_padding.truncate(old_len);
-
-			match mtype {
-				Manifest | ToString if !had_fields => {
-					// Empty object as "{ }"
-					buf.push(' ');
+			Val::Obj(obj) if !obj.is_empty() => {
+				if i == 0 {
+					check_depth_limit(0, options)?;
+					obj.run_assertions()?;
+					buf.push('{');
 				}
-				Manifest => {
-					buf.push_str(options.newline);
-					buf.push_str(cur_padding);
+				if self.fields.is_none() {
+					self.fields = Some(manifested_fields(obj, options)?);
 				}
-				Std => {
-					if !had_fields {
-						// Stdlib formats empty object as "{\n\n}"
-						buf.push_str(options.newline);
-					}
-					buf.push_str(options.newline);
-					buf.push_str(cur_padding);
+				let fields = self.fields.as_ref().expect("fields are collected");
+				let (key, field) = fields[i].clone();
+				let value = obj
+					.get(field.clone())
+					.transpose()
+					.expect("field exists")
+					.with_description(|| format!("field <{field}> evaluation"))?;
+				write_item_separator(i, &mut buf, &cur_padding, options);
+				options.escape_string(&key, &mut buf);
+				buf.push_str(options.key_val_sep);
+				in_description_frame(
+					|| format!("field <{field}> manifestification"),
+					|| manifest_json_ex_buf(&value, &mut buf, &mut cur_padding, 1, options, None),
+				)?;
+				if self.index == fields.len() {
+					self.finished = true;
+					write_container_end(true, &mut buf, "", options);
+					buf.push('}');
 				}
-				Minify | ToString => {}
 			}
-
-			buf.push('}');
+			val => {
+				self.finished = true;
+				manifest_json_ex_buf(val, &mut buf, &mut String::new(), 0, options, None)?;
+			}
 		}
-		Val::Func(_) => bail!("tried to manifest function"),
-	};
-	Ok(())
+		Ok(buf)
+	}
 }
+impl Iterator for JsonChunks<'_, '_> {
+	type Item = Result<String>;
 
-impl ManifestFormat for JsonFormat<'_> {
-	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
-		manifest_json_ex_buf(&val, buf, &mut String::new(), self)
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+		let chunk = self.next_chunk();
+		if chunk.is_err() {
+			self.finished = true;
+		}
+		Some(chunk)
 	}
 }
 
@@ -357,6 +884,14 @@ impl ManifestFormat for ToStringFormat {
 		}
 		JSON_TO_STRING.manifest_buf(val, out)
 	}
+	fn manifest_buf_limited(&self, val: Val, out: &mut String, budget: SizeBudget) -> Result<()> {
+		const JSON_TO_STRING: JsonFormat = JsonFormat::std_to_string_helper();
+		if let Some(str) = val.as_str() {
+			out.push_str(&str);
+			return budget.check(out);
+		}
+		JSON_TO_STRING.manifest_buf_limited(val, out, budget)
+	}
 	fn file_trailing_newline(&self) -> bool {
 		false
 	}
@@ -378,43 +913,76 @@ impl ManifestFormat for StringFormat {
 	}
 }
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct YamlStreamFormat<I> {
 	inner: I,
+	/// Write `---` before the first document too, not only between documents
+	leading_separator: bool,
 	c_document_end: bool,
 	end_newline: bool,
+	/// Write non-array value as a single document, instead of failing
+	wrap_single: bool,
 }
 impl<I> YamlStreamFormat<I> {
 	pub fn std_yaml_stream(inner: I, c_document_end: bool) -> Self {
 		Self {
 			inner,
+			leading_separator: true,
 			c_document_end,
 			// Stdlib format always inserts useless newline at the end
 			end_newline: true,
+			wrap_single: false,
 		}
 	}
 	pub fn cli(inner: I) -> Self {
 		Self {
 			inner,
+			leading_separator: true,
 			c_document_end: true,
 			end_newline: false,
+			wrap_single: false,
 		}
 	}
+	/// Manifest non-array value as a stream with a single document, instead of failing,
+	/// so the same format can be used for both single and multi-document outputs.
+	#[must_use]
+	pub fn with_wrap_single(mut self, wrap_single: bool) -> Self {
+		self.wrap_single = wrap_single;
+		self
+	}
+	/// Omit `---` before the first document, for consumers which reject it.
+	/// Documents after the first are still separated by `---`
+	#[must_use]
+	pub fn with_leading_separator(mut self, leading_separator: bool) -> Self {
+		self.leading_separator = leading_separator;
+		self
+	}
 }
-impl<I: ManifestFormat> ManifestFormat for YamlStreamFormat<I> {
-	fn manifest_buf(&self, val: Val, out: &mut String) -> Result<()> {
-		let Val::Arr(arr) = val else {
-			bail!(
+impl<I: ManifestFormat> YamlStreamFormat<I> {
+	/// `budget` is checked after every written document
+	fn manifest_stream(
+		&self,
+		val: Val,
+		out: &mut String,
+		budget: Option<SizeBudget>,
+	) -> Result<()> {
+		let arr = match val {
+			Val::Arr(arr) => arr,
+			val if self.wrap_single => ArrValue::eager(vec![val]),
+			val => bail!(
 				"output should be array for yaml stream format, got {}",
 				val.value_type()
-			)
+			),
 		};
 		if !arr.is_empty() {
 			for (i, v) in arr.iter().enumerate() {
 				let v = v.with_description(|| format!("elem <{i}> evaluation"))?;
-				out.push_str("---\n");
+				if i != 0 || self.leading_separator {
+					out.push_str("---\n");
+				}
 				in_description_frame(
 					|| format!("elem <{i}> manifestification"),
-					|| self.inner.manifest_buf(v, out),
+					|| manifest_inner(&self.inner, v, out, budget),
 				)?;
 				out.push('\n');
 			}
@@ -425,8 +993,173 @@ impl<I: ManifestFormat> ManifestFormat for YamlStreamFormat<I> {
 		if self.end_newline {
 			out.push('\n');
 		}
+		budget.map_or(Ok(()), |budget| budget.check(out))
+	}
+}
+impl<I: ManifestFormat> ManifestFormat for YamlStreamFormat<I> {
+	fn manifest_buf(&self, val: Val, out: &mut String) -> Result<()> {
+		self.manifest_stream(val, out, None)
+	}
+	fn manifest_buf_limited(&self, val: Val, out: &mut String, budget: SizeBudget) -> Result<()> {
+		self.manifest_stream(val, out, Some(budget))
+	}
+}
+
+/// [`YamlStreamFormat`] for Kubernetes manifests, which reorders documents by their `kind`, so
+/// they can be applied in dependency order.
+///
+/// Documents are sorted by `kind` priority (lower goes first), kinds without priority go after
+/// all prioritized ones (i.e custom resources after their `CustomResourceDefinition`), and documents
+/// without `kind` go last. Documents with equal priority keep their input order.
+pub struct K8sStreamFormat<I> {
+	stream: YamlStreamFormat<I>,
+	kind_priority: FxHashMap<IStr, u32>,
+}
+impl<I> K8sStreamFormat<I> {
+	pub fn new(
+		stream: YamlStreamFormat<I>,
+		kind_priority: impl IntoIterator<Item = (IStr, u32)>,
+	) -> Self {
+		Self {
+			stream,
+			kind_priority: kind_priority.into_iter().collect(),
+		}
+	}
+}
+impl<I: ManifestFormat> K8sStreamFormat<I> {
+	fn manifest_sorted(
+		&self,
+		val: Val,
+		out: &mut String,
+		budget: Option<SizeBudget>,
+	) -> Result<()> {
+		let arr = match val {
+			Val::Arr(arr) => arr,
+			val if self.stream.wrap_single => ArrValue::eager(vec![val]),
+			// Stream format reports the error
+			val => return self.stream.manifest_stream(val, out, budget),
+		};
+		let mut documents = Vec::with_capacity(arr.len());
+		for (i, v) in arr.iter().enumerate() {
+			let v = v.with_description(|| format!("elem <{i}> evaluation"))?;
+			let Val::Obj(obj) = &v else {
+				bail!("elem <{i}> should be an object, got {}", v.value_type());
+			};
+			let kind = obj
+				.get("kind".into())
+				.with_description(|| format!("elem <{i}> kind evaluation"))?;
+			let order = match kind {
+				Some(Val::Str(kind)) => {
+					let kind = kind.into_flat();
+					self.kind_priority
+						.get(&kind)
+						.map_or((1, 0), |priority| (0, *priority))
+				}
+				Some(kind) => bail!(
+					"elem <{i}> kind should be a string, got {}",
+					kind.value_type()
+				),
+				None => (2, 0),
+			};
+			documents.push((order, v));
+		}
+		// Stable, equal priorities keep input order
+		documents.sort_by_key(|(order, _)| *order);
+		let sorted = documents.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+		self.stream
+			.manifest_stream(Val::Arr(ArrValue::eager(sorted)), out, budget)
+	}
+}
+impl<I: ManifestFormat> ManifestFormat for K8sStreamFormat<I> {
+	fn manifest_buf(&self, val: Val, out: &mut String) -> Result<()> {
+		self.manifest_sorted(val, out, None)
+	}
+	fn manifest_buf_limited(&self, val: Val, out: &mut String, budget: SizeBudget) -> Result<()> {
+		self.manifest_sorted(val, out, Some(budget))
+	}
+	fn file_trailing_newline(&self) -> bool {
+		self.stream.file_trailing_newline()
+	}
+}
+
+/// Writes `header` before and `footer` after the output of the inner format, i.e for
+/// `# GENERATED, DO NOT EDIT` preamble.
+///
+/// Header and footer are written verbatim, so they should include the separating newlines themselves.
+pub struct WithHeaderFooter<I> {
+	inner: I,
+	header: String,
+	footer: String,
+}
+impl<I> WithHeaderFooter<I> {
+	pub fn new(inner: I, header: impl Into<String>, footer: impl Into<String>) -> Self {
+		Self {
+			inner,
+			header: header.into(),
+			footer: footer.into(),
+		}
+	}
+	pub fn header(inner: I, header: impl Into<String>) -> Self {
+		Self::new(inner, header, "")
+	}
+	pub fn footer(inner: I, footer: impl Into<String>) -> Self {
+		Self::new(inner, "", footer)
+	}
+}
+impl<I: ManifestFormat> ManifestFormat for WithHeaderFooter<I> {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		buf.push_str(&self.header);
+		self.inner.manifest_buf(val, buf)?;
+		buf.push_str(&self.footer);
 		Ok(())
 	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		buf.push_str(&self.header);
+		budget.check(buf)?;
+		self.inner.manifest_buf_limited(val, buf, budget)?;
+		buf.push_str(&self.footer);
+		budget.check(buf)
+	}
+	fn file_trailing_newline(&self) -> bool {
+		// Footer already ends the file with a newline, another one would produce an empty line
+		if self.footer.ends_with('\n') {
+			return false;
+		}
+		self.inner.file_trailing_newline()
+	}
+}
+
+/// Fails if the inner format produces more than `limit` bytes.
+///
+/// Limit is passed to the inner format as [`SizeBudget`], json and yaml formats and streams check it
+/// after every written value, so the manifestification stops as soon as the limit is exceeded,
+/// without building the whole output. Output written to the buffer by the failed manifestification
+/// is removed.
+pub struct SizeLimited<I> {
+	inner: I,
+	limit: usize,
+}
+impl<I> SizeLimited<I> {
+	pub fn new(inner: I, limit: usize) -> Self {
+		Self { inner, limit }
+	}
+}
+impl<I: ManifestFormat> ManifestFormat for SizeLimited<I> {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		self.manifest_buf_limited(val, buf, SizeBudget::new(buf, self.limit))
+	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		let start = buf.len();
+		let budget = budget.min(SizeBudget::new(buf, self.limit));
+		let result = self.inner.manifest_buf_limited(val, buf, budget);
+		if result.is_err() {
+			buf.truncate(start);
+		}
+		result
+	}
+	fn file_trailing_newline(&self) -> bool {
+		self.inner.file_trailing_newline()
+	}
 }
 
 pub fn escape_string_json(s: &str) -> String {
@@ -435,6 +1168,258 @@ pub fn escape_string_json(s: &str) -> String {
 	buf
 }
 
+/// Debug output of value, which doesn't force evaluation of lazy values
+///
+/// Layout is the same as for [`JsonFormat::debug`], but array elements and object fields which
+/// weren't evaluated yet are written as `<thunk>`, failed ones as `<error: message>`, and functions
+/// as `<function>`. Object assertions are not executed.
+///
+/// Useful to find out, which parts of the value were actually needed by the evaluation.
+pub struct LazyDebugFormat {
+	options: JsonFormat<'static>,
+}
+impl LazyDebugFormat {
+	pub fn new() -> Self {
+		Self {
+			options: JsonFormat::debug(),
+		}
+	}
+	/// Shorten strings longer than `truncate_strings` bytes, same as [`JsonFormat::debug`] does.
+	/// Strings are written in full if `None`
+	#[must_use]
+	pub fn with_truncate_strings(mut self, truncate_strings: Option<usize>) -> Self {
+		self.options.debug_truncate_strings = truncate_strings;
+		self
+	}
+	#[cfg(feature = "exp-preserve-order")]
+	#[must_use]
+	pub fn with_preserve_order(mut self, preserve_order: bool) -> Self {
+		self.options.preserve_order = preserve_order;
+		self
+	}
+
+	fn inspect_peeked(
+		&self,
+		peeked: Option<Result<Val>>,
+		buf: &mut String,
+		cur_padding: &mut String,
+	) -> Result<()> {
+		match peeked {
+			Some(Ok(val)) => self.inspect(val, buf, cur_padding)?,
+			Some(Err(e)) => write!(buf, "<error: {}>", e.error()).unwrap(),
+			None => buf.push_str("<thunk>"),
+		}
+		Ok(())
+	}
+	fn inspect(&self, val: Val, buf: &mut String, cur_padding: &mut String) -> Result<()> {
+		let options = &self.options;
+		let _guard = check_depth()?;
+		let old_len = cur_padding.len();
+		let (had_items, end) = match val {
+			Val::Func(_) => {
+				buf.push_str("<function>");
+				return Ok(());
+			}
+			Val::Arr(arr) => {
+				buf.push('[');
+				cur_padding.push_str(&options.padding);
+				for i in 0..arr.len() {
+					write_item_separator(i, buf, cur_padding, options);
+					let peeked = arr.get_lazy(i).and_then(|v| v.peek());
+					self.inspect_peeked(peeked, buf, cur_padding)?;
+				}
+				(!arr.is_empty(), ']')
+			}
+			Val::Obj(obj) => {
+				let fields = obj.fields(
+					#[cfg(feature = "exp-preserve-order")]
+					options.preserve_order,
+				);
+				buf.push('{');
+				cur_padding.push_str(&options.padding);
+				for (i, field) in fields.iter().enumerate() {
+					write_item_separator(i, buf, cur_padding, options);
+					options.escape_string(field, buf);
+					buf.push_str(options.key_val_sep);
+					self.inspect_peeked(obj.peek(field.clone()), buf, cur_padding)?;
+				}
+				(!fields.is_empty(), '}')
+			}
+			// Everything else has no lazy parts, and is written as is
+			val => {
+				manifest_value_start(val, buf, cur_padding, 0, false, options)?;
+				return Ok(());
+			}
+		};
+		cur_padding.truncate(old_len);
+		write_container_end(had_items, buf, cur_padding, options);
+		buf.push(end);
+		Ok(())
+	}
+}
+impl Default for LazyDebugFormat {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl ManifestFormat for LazyDebugFormat {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		self.inspect(val, buf, &mut String::new())
+	}
+}
+
+/// How array indexes are written in [`FlatPathFormat`] keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlatIndexStyle {
+	/// `a[0]`
+	Brackets,
+	/// `a.0`, using the configured separator
+	Segment,
+}
+
+/// Manifests value as a flat json object, where every key is a path to the leaf value,
+/// i.e `{a: {b: [1, 2]}}` is manifested as `{"a.b[0]": 1, "a.b[1]": 2}`.
+///
+/// Empty arrays and objects are kept as leaves, top-level scalars are manifested as is, and empty
+/// top-level containers are manifested as an empty object.
+/// Path segments are not escaped, so field names containing the separator might produce the same
+/// path as nested fields (`{"a.b": 1}` and `{a: {b: 1}}`), manifestification fails on such collisions.
+///
+/// Useful for reviewing config changes, as every changed leaf is a separate line in the diff.
+pub struct FlatPathFormat {
+	options: JsonFormat<'static>,
+	separator: String,
+	index_style: FlatIndexStyle,
+}
+impl FlatPathFormat {
+	pub fn new() -> Self {
+		Self {
+			options: JsonFormat::default(),
+			separator: ".".to_owned(),
+			index_style: FlatIndexStyle::Brackets,
+		}
+	}
+	/// Separator between path segments, `.` by default
+	#[must_use]
+	pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+		self.separator = separator.into();
+		self
+	}
+	#[must_use]
+	pub fn with_index_style(mut self, index_style: FlatIndexStyle) -> Self {
+		self.index_style = index_style;
+		self
+	}
+	#[cfg(feature = "exp-preserve-order")]
+	#[must_use]
+	pub fn with_preserve_order(mut self, preserve_order: bool) -> Self {
+		self.options.preserve_order = preserve_order;
+		self
+	}
+
+	/// Top-level segments are written without separator, empty field name is a valid segment,
+	/// so path being empty doesn't mean it is the top level
+	fn push_segment(&self, path: &mut String, segment: &str, root: bool) {
+		if !root {
+			path.push_str(&self.separator);
+		}
+		path.push_str(segment);
+	}
+	fn flatten(
+		&self,
+		val: Val,
+		path: &mut String,
+		root: bool,
+		written: &mut FxHashSet<String>,
+		buf: &mut String,
+	) -> Result<()> {
+		let _guard = check_depth()?;
+		let old_len = path.len();
+		match val {
+			Val::Arr(arr) if !arr.is_empty() => {
+				for (i, item) in arr.iter().enumerate() {
+					match self.index_style {
+						FlatIndexStyle::Brackets => write!(path, "[{i}]").unwrap(),
+						FlatIndexStyle::Segment => self.push_segment(path, &i.to_string(), root),
+					}
+					item.and_then(|item| self.flatten(item, path, false, written, buf))
+						.with_description(|| format!("elem <{i}> manifestification"))?;
+					path.truncate(old_len);
+				}
+			}
+			Val::Obj(obj) => {
+				obj.run_assertions()?;
+				let fields = obj.fields(
+					#[cfg(feature = "exp-preserve-order")]
+					self.options.preserve_order,
+				);
+				if fields.is_empty() {
+					// Empty top-level object, manifested as an empty object
+					if root {
+						return Ok(());
+					}
+					return self.write_leaf(Val::Obj(obj), path, written, buf);
+				}
+				for field in fields {
+					self.push_segment(path, &field, root);
+					obj.get(field.clone())
+						.and_then(|value| {
+							self.flatten(value.expect("field exists"), path, false, written, buf)
+						})
+						.with_description(|| format!("field <{field}> manifestification"))?;
+					path.truncate(old_len);
+				}
+			}
+			// Empty top-level array, manifested as an empty object
+			Val::Arr(_) if root => {}
+			val => self.write_leaf(val, path, written, buf)?,
+		}
+		Ok(())
+	}
+	fn write_leaf(
+		&self,
+		val: Val,
+		path: &str,
+		written: &mut FxHashSet<String>,
+		buf: &mut String,
+	) -> Result<()> {
+		if !written.insert(path.to_owned()) {
+			bail!("path <{path}> is produced by multiple leaves");
+		}
+		let options = &self.options;
+		write_item_separator(written.len() - 1, buf, &options.padding, options);
+		options.escape_string(path, buf);
+		buf.push_str(options.key_val_sep);
+		match val {
+			// Only empty containers are leaves
+			Val::Arr(_) => buf.push_str("[]"),
+			Val::Obj(_) => buf.push_str("{}"),
+			val => {
+				manifest_value_start(val, buf, &mut String::new(), 0, false, options)?;
+			}
+		}
+		Ok(())
+	}
+}
+impl Default for FlatPathFormat {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl ManifestFormat for FlatPathFormat {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		if !matches!(val, Val::Arr(_) | Val::Obj(_)) {
+			return self.options.manifest_buf(val, buf);
+		}
+		let mut written = FxHashSet::default();
+		buf.push('{');
+		self.flatten(val, &mut String::new(), true, &mut written, buf)?;
+		write_container_end(!written.is_empty(), buf, "", &self.options);
+		buf.push('}');
+		Ok(())
+	}
+}
+
 // Json string encoding was borrowed from https://github.com/serde-rs/json
 
 const BB: u8 = b'b'; // \x08
@@ -470,32 +1455,97 @@ static ESCAPE: [u8; 256] = [
 ];
 
 pub fn escape_string_json_buf(value: &str, buf: &mut String) {
-	// Safety: we only write correct utf-8 in this function
+	escape_string_json_buf_quoted(value, b'"', buf);
+}
+
+/// Same as [`escape_string_json_buf`], but the string is surrounded by the specified ascii `quote` character,
+/// which is escaped instead of `"`
+pub fn escape_string_json_buf_quoted(value: &str, quote: u8, buf: &mut String) {
+	// Safety: we only write correct utf-8 in this function, quote is ascii
 	let buf: &mut Vec<u8> = unsafe { &mut *ptr::from_mut(buf).cast::<Vec<u8>>() };
+	escape_string_json_sink(value, quote, false, buf).unwrap_or_else(|e| match e {});
+}
+
+/// Same as [`escape_string_json_buf`], but the escaped string is written directly to `out`
+///
+/// Escaped string is written in multiple small chunks, so `out` should be buffered.
+pub fn escape_string_json_write(value: &str, out: &mut dyn io::Write) -> io::Result<()> {
+	escape_string_json_sink(value, b'"', false, &mut IoSink(out))
+}
+
+/// Output of [`escape_string_json_sink`]
+trait EscapeSink {
+	type Error;
+	/// Hint, that at least `additional` bytes are going to be written
+	fn reserve(&mut self, _additional: usize) {}
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+impl EscapeSink for Vec<u8> {
+	type Error = Infallible;
+	fn reserve(&mut self, additional: usize) {
+		Self::reserve(self, additional);
+	}
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.extend_from_slice(bytes);
+		Ok(())
+	}
+}
+struct IoSink<'w>(&'w mut dyn io::Write);
+impl EscapeSink for IoSink<'_> {
+	type Error = io::Error;
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.0.write_all(bytes)
+	}
+}
+
+fn escape_string_json_sink<S: EscapeSink>(
+	value: &str,
+	quote: u8,
+	escape_forward_slash: bool,
+	out: &mut S,
+) -> Result<(), S::Error> {
+	debug_assert!(matches!(quote, b'"' | b'\''), "unsupported quote");
 	let bytes = value.as_bytes();
 
 	// Perfect for ascii strings, removes any reallocations
-	buf.reserve(value.len() + 2);
+	out.reserve(value.len() + 2);
+
+	out.write_bytes(&[quote])?;
+
+	let escape_for = |byte: u8| {
+		if byte == quote {
+			quote
+		} else if byte == QU {
+			// Double quote only needs to be escaped when it is used for quoting
+			__
+		} else if byte == b'/' && escape_forward_slash {
+			b'/'
+		} else {
+			ESCAPE[byte as usize]
+		}
+	};
 
-	buf.push(b'"');
+	// Most strings (especially object keys) need no escaping at all, in which case they are
+	// written with a single call, without going through the escaping loop
+	let Some(first_escape) = bytes.iter().position(|&byte| escape_for(byte) != __) else {
+		out.write_bytes(bytes)?;
+		return out.write_bytes(&[quote]);
+	};
 
 	let mut start = 0;
 
-	for (i, &byte) in bytes.iter().enumerate() {
-		let escape = ESCAPE[byte as usize];
+	for (i, &byte) in bytes.iter().enumerate().skip(first_escape) {
+		let escape = escape_for(byte);
 		if escape == __ {
 			continue;
 		}
 
 		if start < i {
-			buf.extend_from_slice(&bytes[start..i]);
+			out.write_bytes(&bytes[start..i])?;
 		}
 		start = i + 1;
 
 		match escape {
-			self::BB | self::TT | self::NN | self::FF | self::RR | self::QU | self::BS => {
-				buf.extend_from_slice(&[b'\\', escape]);
-			}
 			self::UU => {
 				static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
 				let bytes = &[
@@ -506,17 +1556,16 @@ pub fn escape_string_json_buf(value: &str, buf: &mut String) {
 					HEX_DIGITS[(byte >> 4) as usize],
 					HEX_DIGITS[(byte & 0xF) as usize],
 				];
-				buf.extend_from_slice(bytes);
+				out.write_bytes(bytes)?;
+			}
+			escape => {
+				out.write_bytes(&[b'\\', escape])?;
 			}
-			_ => unreachable!(),
 		}
 	}
 
-	if start == bytes.len() {
-		buf.push(b'"');
-		return;
+	if start != bytes.len() {
+		out.write_bytes(&bytes[start..])?;
 	}
-
-	buf.extend_from_slice(&bytes[start..]);
-	buf.push(b'"');
+	out.write_bytes(&[quote])
 }
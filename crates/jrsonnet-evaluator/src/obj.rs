@@ -197,6 +197,12 @@ pub trait ObjectLike: Trace + Any + Debug {
 
 	fn get_for(&self, key: IStr, this: ObjValue) -> Result<Option<Val>>;
 	fn get_for_uncached(&self, key: IStr, this: ObjValue) -> Result<Option<Val>>;
+	/// Get cached result of [`Self::get_for`], without evaluating the field
+	///
+	/// Returns `None` if the field wasn't evaluated yet, or object doesn't cache field values.
+	fn peek_for(&self, _key: IStr, _this: ObjValue) -> Option<Result<Option<Val>>> {
+		None
+	}
 	fn field_visibility(&self, field: IStr) -> Option<Visibility>;
 
 	fn run_assertions_raw(&self, this: ObjValue) -> Result<()>;
@@ -321,6 +327,10 @@ impl ObjectLike for ThisOverride {
 		self.inner.get_raw(key, this)
 	}
 
+	fn peek_for(&self, key: IStr, this: ObjValue) -> Option<Result<Option<Val>>> {
+		self.inner.0.peek_for(key, this)
+	}
+
 	fn field_visibility(&self, field: IStr) -> Option<Visibility> {
 		self.inner.field_visibility(field)
 	}
@@ -376,6 +386,14 @@ impl ObjValue {
 	pub fn is_empty(&self) -> bool {
 		self.0.is_empty()
 	}
+	/// Same as [`Self::is_empty`], but optionally also considers hidden fields.
+	///
+	/// Unlike [`Self::is_empty`], stops on the first found field instead of counting all of them.
+	pub fn is_empty_ex(&self, include_hidden: bool) -> bool {
+		!self.enum_fields(SuperDepth::default(), &mut |_, _, name, _| {
+			include_hidden || self.has_field(name)
+		})
+	}
 	pub fn enum_fields(&self, depth: SuperDepth, handler: &mut EnumFieldsHandler<'_>) -> bool {
 		self.0.enum_fields(depth, handler)
 	}
@@ -403,6 +421,14 @@ impl ObjValue {
 		self.0.get_for(key, this)
 	}
 
+	/// Get field value, if it was already evaluated, without evaluating it or running assertions
+	///
+	/// Returns `None` if the field doesn't exist, or wasn't evaluated yet.
+	pub fn peek(&self, key: IStr) -> Option<Result<Val>> {
+		let this = self.0.this().unwrap_or_else(|| self.clone());
+		self.0.peek_for(key, this)?.transpose()
+	}
+
 	pub fn get_or_bail(&self, key: IStr) -> Result<Val> {
 		let Some(value) = self.get(key.clone())? else {
 			let suggestions = suggest_object_fields(self, key.clone());
@@ -512,6 +538,11 @@ impl ObjValue {
 		);
 		out
 	}
+	/// With `preserve_order`, fields are ordered by their first definition: for `a + b`, fields of `a`
+	/// come first, in the order of definition, then new fields of `b`. Fields overridden in `b`
+	/// (including `+:` and visibility changes) keep their position in `a`.
+	///
+	/// Otherwise, fields are sorted by name.
 	pub fn fields_ex(
 		&self,
 		include_hidden: bool,
@@ -747,6 +778,15 @@ impl ObjectLike for OopObject {
 		);
 		Ok(value)
 	}
+	fn peek_for(&self, key: IStr, this: ObjValue) -> Option<Result<Option<Val>>> {
+		let cache_key = (key, Some(this.downgrade()));
+		match self.value_cache.borrow().get(&cache_key)? {
+			CacheValue::Cached(v) => Some(Ok(Some(v.clone()))),
+			CacheValue::NotFound => Some(Ok(None)),
+			CacheValue::Errored(e) => Some(Err(e.clone())),
+			CacheValue::Pending => None,
+		}
+	}
 	fn get_for_uncached(&self, key: IStr, real_this: ObjValue) -> Result<Option<Val>> {
 		match (self.this_entries.get(&key), &self.sup) {
 			(Some(k), None) => Ok(Some(self.evaluate_this(k, real_this)?)),
@@ -994,3 +1034,57 @@ impl ObjMemberBuilder<ExtendBuilder<'_>> {
 		*receiver.0 = new.extend_with_raw_member(name, member);
 	}
 }
+
+#[cfg(all(test, feature = "exp-preserve-order"))]
+mod tests {
+	use crate::{manifest::JsonFormat, State};
+
+	// Expected outputs match go-jsonnet with `preserve_order`
+	#[test]
+	fn preserve_order_merge() {
+		let s = State::default();
+		for (code, expected) in [
+			("{b: 1, a: 2} + {c: 3, a: 4}", r#"{"b":1,"a":4,"c":3}"#),
+			("{c: 1} + {b: 2, a: 3, c: 4}", r#"{"c":4,"b":2,"a":3}"#),
+			(
+				"{z: 1, y: 2} + {x: 3} + {y: 5, w: 6}",
+				r#"{"z":1,"y":5,"x":3,"w":6}"#,
+			),
+			(
+				"({d: 1} + {c: 2}) + ({b: 3} + {d: 4, a: 5})",
+				r#"{"d":4,"c":2,"b":3,"a":5}"#,
+			),
+			("{b: 1, a: 2} + {c: 3, a+: 4}", r#"{"b":1,"a":6,"c":3}"#),
+			(
+				"{a: {z: 1, y: 2}} + {a+: {x: 3, z: 4}}",
+				r#"{"a":{"z":4,"y":2,"x":3}}"#,
+			),
+			(
+				"{b: 1, a: 2, c:: 0} + {c: 3, d: super.a}",
+				r#"{"b":1,"a":2,"d":2}"#,
+			),
+			// Hidden field stays hidden when overridden with `:`, and keeps its position when unhidden
+			("{b: 1, a:: 2} + {c: 3, a: 4}", r#"{"b":1,"c":3}"#),
+			("{b: 1, a:: 2} + {c: 3, a::: 4}", r#"{"b":1,"a":4,"c":3}"#),
+			(
+				"{b: 1, a: 2} + {b:: 3} + {b::: 4, c: 5}",
+				r#"{"b":4,"a":2,"c":5}"#,
+			),
+			(
+				"{local x = 1, b: x, assert true, a: 2} + {c: 3}",
+				r#"{"b":1,"a":2,"c":3}"#,
+			),
+			(
+				"{[k]: 1 for k in ['z', 'a', 'm']} + {b: 1, z: 2}",
+				r#"{"z":2,"a":1,"m":1,"b":1}"#,
+			),
+		] {
+			let v = s.evaluate_snippet("snip", code).expect("evaluates");
+			assert_eq!(
+				v.manifest(JsonFormat::minify(true)).expect("manifests"),
+				expected,
+				"{code}"
+			);
+		}
+	}
+}
@@ -17,8 +17,8 @@ use crate::{
 pub enum FormatError {
 	#[error("truncated format code")]
 	TruncatedFormatCode,
-	#[error("unrecognized conversion type: {0}")]
-	UnrecognizedConversionType(char),
+	#[error("unrecognized conversion type <{0}> in <{1}>")]
+	UnrecognizedConversionType(char, IStr),
 
 	#[error("not enough values")]
 	NotEnoughValues,
@@ -225,7 +225,10 @@ pub fn parse_conversion_type(str: &str) -> ParseResult<'_, ConvType> {
 		b'c' => (ConvTypeV::Char, false),
 		b's' => (ConvTypeV::String, false),
 		b'%' => (ConvTypeV::Percent, false),
-		c => return Err(UnrecognizedConversionType(c as char)),
+		_ => {
+			let c = str.chars().next().expect("not empty");
+			return Err(UnrecognizedConversionType(c, IStr::empty()));
+		}
 	};
 
 	Ok((ConvType { v: v.0, caps: v.1 }, &str[1..]))
@@ -244,12 +247,20 @@ pub fn parse_code(str: &str) -> ParseResult<'_, Code<'_>> {
 	if str.is_empty() {
 		return Err(TruncatedFormatCode);
 	}
+	let full = str;
 	let (mkey, str) = try_parse_mapping_key(str)?;
 	let (cflags, str) = try_parse_cflags(str)?;
 	let (width, str) = try_parse_field_width(str)?;
 	let (precision, str) = try_parse_precision(str)?;
-	let ((), str) = try_parse_length_modifier(str)?;
-	let (convtype, str) = parse_conversion_type(str)?;
+	let ((), rest) = try_parse_length_modifier(str)?;
+	let (convtype, str) = parse_conversion_type(rest).map_err(|e| match e {
+		UnrecognizedConversionType(c, _) => {
+			// Whole directive, from % to the conversion type
+			let len = full.len() - rest.len() + c.len_utf8();
+			UnrecognizedConversionType(c, format!("%{}", &full[..len]).into())
+		}
+		e => e,
+	})?;
 
 	Ok((
 		Code {
@@ -820,6 +831,41 @@ pub mod test_format {
 		assert_eq!(format_arr("%+-04o", &[num(8.0)]).unwrap(), "+10 ");
 	}
 
+	#[test]
+	fn conversions() {
+		assert_eq!(format_arr("%5.2f", &[num(3.14159)]).unwrap(), " 3.14");
+		assert_eq!(
+			format_arr("%x %X %#x", &[num(255.0), num(255.0), num(255.0)]).unwrap(),
+			"ff FF 0xff"
+		);
+		assert_eq!(format_arr("%e", &[num(12345.678)]).unwrap(), "1.234568e+04");
+		assert_eq!(
+			format_arr("%g %g", &[num(0.000_012_34), num(100.0)]).unwrap(),
+			"1.234e-05 100"
+		);
+		assert_eq!(
+			format_arr("%c%c", &[num(65.0), Val::string("z")]).unwrap(),
+			"Az"
+		);
+		assert_eq!(
+			format_arr("%-5d|%05d", &[num(42.0), num(42.0)]).unwrap(),
+			"42   |00042"
+		);
+	}
+
+	#[test]
+	fn unrecognized_conversion() {
+		assert_eq!(
+			parse_code("-3q rest").unwrap_err().to_string(),
+			"unrecognized conversion type <q> in <%-3q>"
+		);
+		assert_eq!(
+			parse_code("(a)ж").unwrap_err().to_string(),
+			"unrecognized conversion type <ж> in <%(a)ж>"
+		);
+		assert!(format_arr("%5.2f %-3q", &[num(1.0), num(2.0)]).is_err());
+	}
+
 	#[test]
 	fn percent_doesnt_consumes_values() {
 		assert_eq!(
@@ -274,6 +274,30 @@ impl Typed for usize {
 	}
 }
 
+impl Typed for i64 {
+	const TYPE: &'static ComplexValType =
+		&ComplexValType::BoundedNumber(Some(MIN_SAFE_INTEGER), Some(MAX_SAFE_INTEGER));
+
+	fn into_untyped(value: Self) -> Result<Val> {
+		Ok(Val::try_num(value)?)
+	}
+
+	fn from_untyped(value: Val) -> Result<Self> {
+		<Self as Typed>::TYPE.check(&value)?;
+		match value {
+			Val::Num(n) => {
+				let n = n.get();
+				#[allow(clippy::float_cmp)]
+				if n.trunc() != n {
+					bail!("cannot convert number with fractional part to i64")
+				}
+				Ok(n as Self)
+			}
+			_ => unreachable!(),
+		}
+	}
+}
+
 impl Typed for IStr {
 	const TYPE: &'static ComplexValType = &ComplexValType::Simple(ValType::Str);
 
@@ -98,6 +98,17 @@ where
 		*self.0.borrow_mut() = ThunkInner::Computed(new_value.clone());
 		Ok(new_value)
 	}
+
+	/// Get evaluation result, if thunk was already evaluated, without forcing evaluation
+	///
+	/// Returns `None` for thunks which are not evaluated yet, or are being evaluated right now.
+	pub fn peek(&self) -> Option<Result<T>> {
+		match &*self.0.borrow() {
+			ThunkInner::Computed(v) => Some(Ok(v.clone())),
+			ThunkInner::Errored(e) => Some(Err(e.clone())),
+			ThunkInner::Waiting(..) | ThunkInner::Pending => None,
+		}
+	}
 }
 
 pub trait ThunkMapper<Input>: Trace {
@@ -1,13 +1,18 @@
 #![allow(non_snake_case)]
 
+use std::{cell::RefCell, collections::HashMap, num::NonZeroU32, rc::Rc};
+
 use jrsonnet_evaluator::{
 	bail,
+	error::ErrorKind::InfiniteRecursionDetected,
 	function::{builtin, FuncVal},
 	runtime_error,
-	typed::{BoundedI32, BoundedUsize, Either2, NativeFn, Typed},
-	val::{equals, ArrValue, IndexableVal},
-	Either, IStr, ObjValue, ObjValueBuilder, Result, ResultExt, Thunk, Val,
+	stack::check_depth,
+	typed::{BoundedI32, BoundedUsize, Either2, NativeFn, Null, Typed},
+	val::{equals, ArrValue, ArrayLike, IndexableVal, ThunkValue},
+	Context, Either, Error, IStr, ObjValue, ObjValueBuilder, Result, ResultExt, Thunk, Val,
 };
+use jrsonnet_gcmodule::{Cc, Trace};
 
 pub fn eval_on_empty(on_empty: Option<Thunk<Val>>) -> Result<Val> {
 	if let Some(on_empty) = on_empty {
@@ -18,10 +23,15 @@ pub fn eval_on_empty(on_empty: Option<Thunk<Val>>) -> Result<Val> {
 }
 
 #[builtin]
-pub fn builtin_make_array(sz: BoundedI32<0, { i32::MAX }>, func: FuncVal) -> Result<ArrValue> {
+pub fn builtin_make_array(
+	ctx: Context,
+	sz: BoundedI32<0, { i32::MAX }>,
+	func: FuncVal,
+) -> Result<ArrValue> {
 	if *sz == 0 {
 		return Ok(ArrValue::empty());
 	}
+	ctx.state().check_allocation(*sz as usize)?;
 	func.evaluate_trivial().map_or_else(
 		|| Ok(ArrValue::range_exclusive(0, *sz).map(func)),
 		|trivial| {
@@ -34,27 +44,189 @@ pub fn builtin_make_array(sz: BoundedI32<0, { i32::MAX }>, func: FuncVal) -> Res
 	)
 }
 
+#[derive(Trace)]
+struct DeepArrayCell {
+	dims: Rc<Vec<u32>>,
+	index: Vec<u32>,
+	f: FuncVal,
+}
+impl ThunkValue for DeepArrayCell {
+	type Output = Val;
+
+	fn get(self: Box<Self>) -> Result<Val> {
+		if self.index.len() == self.dims.len() {
+			let index = ArrValue::eager(self.index.iter().map(|&i| Val::Num(i.into())).collect());
+			return self.f.evaluate_simple(&(index,), false);
+		}
+		Ok(Val::Arr(make_array_deep_level(
+			&self.dims,
+			&self.index,
+			&self.f,
+		)))
+	}
+}
+
+fn make_array_deep_level(dims: &Rc<Vec<u32>>, prefix: &[u32], f: &FuncVal) -> ArrValue {
+	ArrValue::lazy(
+		(0..dims[prefix.len()])
+			.map(|i| {
+				let mut index = Vec::with_capacity(prefix.len() + 1);
+				index.extend_from_slice(prefix);
+				index.push(i);
+				Thunk::new(DeepArrayCell {
+					dims: dims.clone(),
+					index,
+					f: f.clone(),
+				})
+			})
+			.collect(),
+	)
+}
+
+/// Builds nested array of the shape described by `dims`, calling `f` with index tuple for every cell.
+///
+/// i.e `std.makeArrayDeep([2, 2], function(i) i[0] * 2 + i[1]) == [[0, 1], [2, 3]]`
+///
+/// Cells are evaluated lazily, `f` is only called for accessed ones.
+#[builtin]
+pub fn builtin_make_array_deep(dims: ArrValue, f: FuncVal) -> Result<ArrValue> {
+	if dims.is_empty() {
+		bail!("dims should not be empty");
+	}
+	let mut sizes = Vec::with_capacity(dims.len());
+	for (i, dim) in dims.iter().enumerate() {
+		let dim = dim.with_description(|| format!("dims elem <{i}> evaluation"))?;
+		let Val::Num(n) = dim else {
+			bail!(
+				"dims elem <{i}> should be a number, got {}",
+				dim.value_type()
+			);
+		};
+		let n = n.get();
+		#[allow(clippy::float_cmp)]
+		if n < 0.0 || n.trunc() != n || n > f64::from(i32::MAX) {
+			bail!("dims elem <{i}> should be a non-negative integer, got {n}");
+		}
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		sizes.push(n as u32);
+	}
+	Ok(make_array_deep_level(&Rc::new(sizes), &[], &f))
+}
+
 #[builtin]
-pub fn builtin_repeat(what: Either![IStr, ArrValue], count: usize) -> Result<Val> {
+pub fn builtin_repeat(ctx: Context, what: Either![IStr, ArrValue], count: usize) -> Result<Val> {
 	Ok(match what {
-		Either2::A(s) => Val::string(s.repeat(count)),
-		Either2::B(arr) => Val::Arr(
-			ArrValue::repeated(arr, count)
-				.ok_or_else(|| runtime_error!("repeated length overflow"))?,
-		),
+		Either2::A(s) => {
+			if s.is_empty() || count == 0 {
+				return Ok(Val::string(""));
+			}
+			let len = s
+				.len()
+				.checked_mul(count)
+				.ok_or_else(|| runtime_error!("repeated length overflow"))?;
+			ctx.state().check_allocation(len)?;
+			let mut out = String::new();
+			// `str::repeat` aborts the process on allocation failure
+			out.try_reserve_exact(len)
+				.map_err(|e| runtime_error!("can't allocate repeated string: {e}"))?;
+			for _ in 0..count {
+				out.push_str(&s);
+			}
+			Val::string(out)
+		}
+		Either2::B(arr) => {
+			if arr.is_empty() || count == 0 {
+				return Ok(Val::Arr(ArrValue::empty()));
+			}
+			let repeated = ArrValue::repeated(arr, count)
+				.ok_or_else(|| runtime_error!("repeated length overflow"))?;
+			ctx.state().check_allocation(repeated.len())?;
+			Val::Arr(repeated)
+		}
 	})
 }
 
+/// Python-like slicing, same as `indexable[index:end:step]`, except `step` might be negative,
+/// in which case elements are taken in reverse order, from `index` (defaults to the last element)
+/// down to `end` (exclusive, defaults to the first element).
+///
+/// `null` is accepted in place of any omitted argument, as in go-jsonnet.
 #[builtin]
 pub fn builtin_slice(
 	indexable: IndexableVal,
-	index: Option<i32>,
-	end: Option<i32>,
-	step: Option<BoundedUsize<1, { i32::MAX as usize }>>,
+	index: Option<Either![i32, Null]>,
+	end: Option<Either![i32, Null]>,
+	step: Option<Either![i32, Null]>,
 ) -> Result<Val> {
+	let non_null = |v: Option<Either![i32, Null]>| match v {
+		Some(Either2::A(v)) => Some(v),
+		Some(Either2::B(Null)) | None => None,
+	};
+	let (index, end) = (non_null(index), non_null(end));
+	let step = match non_null(step) {
+		Some(0) => bail!("slice step can't be zero"),
+		Some(step) if step < 0 => {
+			return Ok(slice_reversed(indexable, index, end, step.unsigned_abs()).into())
+		}
+		step => step.map(|step| {
+			#[allow(clippy::cast_sign_loss)]
+			BoundedUsize::new(step as usize).expect("step is positive")
+		}),
+	};
 	indexable.slice(index, end, step).map(Val::from)
 }
 
+/// Slice with negative step, `step` is its absolute value
+#[allow(
+	clippy::cast_sign_loss,
+	clippy::cast_possible_wrap,
+	clippy::cast_possible_truncation
+)]
+fn slice_reversed(
+	indexable: IndexableVal,
+	index: Option<i32>,
+	end: Option<i32>,
+	step: u32,
+) -> IndexableVal {
+	let len = match &indexable {
+		IndexableVal::Str(s) => s.chars().count(),
+		IndexableVal::Arr(a) => a.len(),
+	};
+	// Positions in the reversed value, `len - 1 - i` for the original index `i`, clamped to `0..=len`
+	let reversed_pos = |pos: i32| {
+		let pos = if pos < 0 {
+			i64::from(pos) + len as i64
+		} else {
+			i64::from(pos)
+		};
+		(len as i64 - 1 - pos).clamp(0, len as i64) as usize
+	};
+	let from = index.map_or(0, reversed_pos);
+	let to = end.map_or(len, reversed_pos);
+	if from >= to {
+		return match indexable {
+			IndexableVal::Str(_) => IndexableVal::Str("".into()),
+			IndexableVal::Arr(_) => IndexableVal::Arr(ArrValue::empty()),
+		};
+	}
+	match indexable {
+		IndexableVal::Str(s) => IndexableVal::Str(
+			s.chars()
+				.rev()
+				.skip(from)
+				.take(to - from)
+				.step_by(step as usize)
+				.collect::<String>()
+				.into(),
+		),
+		IndexableVal::Arr(a) => IndexableVal::Arr(a.reversed().slice(
+			Some(from as i32),
+			Some(to as i32),
+			NonZeroU32::new(step),
+		)),
+	}
+}
+
 #[builtin]
 pub fn builtin_map(func: FuncVal, arr: IndexableVal) -> ArrValue {
 	let arr = arr.to_array();
@@ -153,6 +325,176 @@ pub fn builtin_foldr(func: FuncVal, arr: ArrValue, init: Val) -> Result<Val> {
 	Ok(acc)
 }
 
+/// Same as `foldl`, but `func` is called as `func(acc, index, elem)`
+#[builtin]
+pub fn builtin_foldl_indexed(func: FuncVal, arr: ArrValue, init: Val) -> Result<Val> {
+	let mut acc = init;
+	for (idx, i) in arr.iter().enumerate() {
+		acc = func.evaluate_simple(&(acc, idx, i?), false)?;
+	}
+	Ok(acc)
+}
+
+/// Same as `foldr`, but `func` is called as `func(index, elem, acc)`
+#[builtin]
+pub fn builtin_foldr_indexed(func: FuncVal, arr: ArrValue, init: Val) -> Result<Val> {
+	let mut acc = init;
+	for (idx, i) in arr.iter().enumerate().rev() {
+		acc = func.evaluate_simple(&(idx, i?, acc), false)?;
+	}
+	Ok(acc)
+}
+
+#[derive(Trace, Debug, Clone)]
+enum ScanState {
+	Waiting,
+	Pending,
+	Computed(Val),
+	Errored(Error),
+}
+
+/// Lazy result of `std.scanl`/`std.scanr`
+///
+/// Accumulator values are computed on access, starting from the last already computed one,
+/// iteratively, so accessing the last element of a long scan doesn't recurse.
+#[derive(Trace, Debug, Clone)]
+struct ScanArray {
+	func: FuncVal,
+	arr: ArrValue,
+	/// `scanr`, accumulation goes from the last element to the first
+	right: bool,
+	/// Indexed by step, `0` being the initial value
+	states: Cc<RefCell<Vec<ScanState>>>,
+}
+impl ScanArray {
+	fn new(func: FuncVal, arr: ArrValue, init: Val, right: bool) -> Self {
+		let mut states = vec![ScanState::Waiting; arr.len() + 1];
+		states[0] = ScanState::Computed(init);
+		Self {
+			func,
+			arr,
+			right,
+			states: Cc::new(RefCell::new(states)),
+		}
+	}
+	fn step_value(&self, step: usize) -> Result<Val> {
+		let from = {
+			let states = self.states.borrow();
+			let mut from = step;
+			loop {
+				match &states[from] {
+					ScanState::Computed(_) => break,
+					ScanState::Errored(e) => return Err(e.clone()),
+					ScanState::Pending => bail!(InfiniteRecursionDetected),
+					// Initial value is always computed, so this never goes below 0
+					ScanState::Waiting => from -= 1,
+				}
+			}
+			from
+		};
+		for step in from + 1..=step {
+			let acc = match &self.states.borrow()[step - 1] {
+				ScanState::Computed(acc) => acc.clone(),
+				_ => unreachable!("previous step is computed"),
+			};
+			self.states.borrow_mut()[step] = ScanState::Pending;
+
+			let index = if self.right {
+				self.arr.len() - step
+			} else {
+				step - 1
+			};
+			let result = self
+				.arr
+				.get(index)
+				.transpose()
+				.expect("index is in bounds")
+				.with_description(|| format!("elem <{index}> evaluation"))
+				.and_then(|elem| {
+					if self.right {
+						self.func.evaluate_simple(&(elem, acc), false)
+					} else {
+						self.func.evaluate_simple(&(acc, elem), false)
+					}
+				})
+				.with_description(|| format!("scan step <{index}>"));
+			self.states.borrow_mut()[step] = match &result {
+				Ok(v) => ScanState::Computed(v.clone()),
+				Err(e) => ScanState::Errored(e.clone()),
+			};
+			result?;
+		}
+		let ScanState::Computed(v) = &self.states.borrow()[step] else {
+			unreachable!("step is computed")
+		};
+		Ok(v.clone())
+	}
+	fn step_of(&self, index: usize) -> usize {
+		if self.right {
+			self.arr.len() - index
+		} else {
+			index
+		}
+	}
+}
+impl ArrayLike for ScanArray {
+	fn len(&self) -> usize {
+		self.arr.len() + 1
+	}
+	fn get(&self, index: usize) -> Result<Option<Val>> {
+		if index >= self.len() {
+			return Ok(None);
+		}
+		self.step_value(self.step_of(index)).map(Some)
+	}
+	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
+		#[derive(Trace)]
+		struct ScanElement {
+			arr: ScanArray,
+			index: usize,
+		}
+		impl ThunkValue for ScanElement {
+			type Output = Val;
+
+			fn get(self: Box<Self>) -> Result<Val> {
+				Ok(self.arr.get(self.index)?.expect("index checked"))
+			}
+		}
+
+		if index >= self.len() {
+			return None;
+		}
+		Some(Thunk::new(ScanElement {
+			arr: self.clone(),
+			index,
+		}))
+	}
+	fn get_cheap(&self, _index: usize) -> Option<Val> {
+		None
+	}
+	fn is_cheap(&self) -> bool {
+		false
+	}
+}
+
+/// Same as `foldl`, but returns all the intermediate accumulator values, starting with `init`,
+/// i.e `std.scanl(function(acc, x) acc + x, [1, 2, 3], 0) == [0, 1, 3, 6]`
+///
+/// Values are computed lazily, only up to the accessed element.
+#[builtin]
+pub fn builtin_scanl(func: FuncVal, arr: ArrValue, init: Val) -> ArrValue {
+	ArrValue::new(ScanArray::new(func, arr, init, false))
+}
+
+/// Same as `foldr`, but returns all the intermediate accumulator values, ending with `init`,
+/// i.e `std.scanr(function(x, acc) acc + x, [1, 2, 3], 0) == [6, 5, 3, 0]`
+///
+/// Values are computed lazily, only down to the accessed element.
+#[builtin]
+pub fn builtin_scanr(func: FuncVal, arr: ArrValue, init: Val) -> ArrValue {
+	ArrValue::new(ScanArray::new(func, arr, init, true))
+}
+
 #[builtin]
 pub fn builtin_range(from: i32, to: i32) -> Result<ArrValue> {
 	if to < from {
@@ -161,8 +503,149 @@ pub fn builtin_range(from: i32, to: i32) -> Result<ArrValue> {
 	Ok(ArrValue::range_inclusive(from, to))
 }
 
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+enum RangeBound {
+	Small(i64),
+	#[cfg(feature = "exp-bigint")]
+	Big(num_bigint::BigInt),
+}
+impl RangeBound {
+	#[allow(clippy::float_cmp)]
+	fn from_val(v: Val) -> Result<Self> {
+		#[cfg(feature = "exp-bigint")]
+		if let Val::BigInt(v) = v {
+			return Ok(Self::Big(*v));
+		}
+		let v = f64::from_untyped(v)?;
+		if v.trunc() != v {
+			bail!("expected integer, got {v}");
+		}
+		if v.abs() > MAX_SAFE_INTEGER {
+			bail!("integer {v:e} is outside of safe range");
+		}
+		#[allow(clippy::cast_possible_truncation)]
+		Ok(Self::Small(v as i64))
+	}
+	#[cfg(feature = "exp-bigint")]
+	fn into_big(self) -> num_bigint::BigInt {
+		match self {
+			Self::Small(v) => v.into(),
+			Self::Big(v) => v,
+		}
+	}
+}
+
+#[derive(Trace, Debug)]
+struct SteppedRangeArray {
+	from: i64,
+	step: i64,
+	len: usize,
+}
+impl ArrayLike for SteppedRangeArray {
+	fn len(&self) -> usize {
+		self.len
+	}
+	fn get(&self, index: usize) -> Result<Option<Val>> {
+		Ok(self.get_cheap(index))
+	}
+	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
+		self.get_cheap(index).map(Thunk::evaluated)
+	}
+	fn get_cheap(&self, index: usize) -> Option<Val> {
+		if index >= self.len {
+			return None;
+		}
+		// Index is less than length, which was computed from i64 values
+		#[allow(clippy::cast_possible_wrap)]
+		let value = self.from + self.step * index as i64;
+		Some(Val::Num(
+			value
+				.try_into()
+				.expect("range elements are between safe bounds"),
+		))
+	}
+	fn is_cheap(&self) -> bool {
+		true
+	}
+}
+
+#[cfg(feature = "exp-bigint")]
+#[derive(Trace, Debug)]
+struct BigSteppedRangeArray {
+	#[trace(skip)]
+	from: num_bigint::BigInt,
+	#[trace(skip)]
+	step: num_bigint::BigInt,
+	len: usize,
+}
+#[cfg(feature = "exp-bigint")]
+impl ArrayLike for BigSteppedRangeArray {
+	fn len(&self) -> usize {
+		self.len
+	}
+	fn get(&self, index: usize) -> Result<Option<Val>> {
+		Ok(self.get_cheap(index))
+	}
+	fn get_lazy(&self, index: usize) -> Option<Thunk<Val>> {
+		self.get_cheap(index).map(Thunk::evaluated)
+	}
+	fn get_cheap(&self, index: usize) -> Option<Val> {
+		if index >= self.len {
+			return None;
+		}
+		Some(Val::BigInt(Box::new(&self.from + &self.step * index)))
+	}
+	fn is_cheap(&self) -> bool {
+		true
+	}
+}
+
+/// Inclusive range from `from` toward `to`, going by `step`, which may be negative for descending ranges.
+///
+/// i.e `std.rangeStep(0, 10, 5) == [0, 5, 10]`, `std.rangeStep(3, 0, -2) == [3, 1]`
+///
+/// With `exp-bigint`, if any of the arguments is a bigint, range elements are bigints.
+#[builtin]
+pub fn builtin_range_step(from: Val, to: Val, step: Val) -> Result<ArrValue> {
+	match (
+		RangeBound::from_val(from)?,
+		RangeBound::from_val(to)?,
+		RangeBound::from_val(step)?,
+	) {
+		(RangeBound::Small(from), RangeBound::Small(to), RangeBound::Small(step)) => {
+			if step == 0 {
+				bail!("range step can't be zero");
+			}
+			if (step > 0 && to < from) || (step < 0 && to > from) {
+				return Ok(ArrValue::empty());
+			}
+			// Bounds are in safe integer range, so their difference can't overflow
+			let Ok(len) = usize::try_from((to - from) / step + 1) else {
+				bail!("range is too large");
+			};
+			Ok(ArrValue::new(SteppedRangeArray { from, step, len }))
+		}
+		#[cfg(feature = "exp-bigint")]
+		(from, to, step) => {
+			use num_bigint::Sign;
+			let (from, to, step) = (from.into_big(), to.into_big(), step.into_big());
+			match step.sign() {
+				Sign::NoSign => bail!("range step can't be zero"),
+				Sign::Plus if to < from => return Ok(ArrValue::empty()),
+				Sign::Minus if to > from => return Ok(ArrValue::empty()),
+				_ => {}
+			}
+			let Ok(len) = usize::try_from((&to - &from) / &step + 1u8) else {
+				bail!("range is too large");
+			};
+			Ok(ArrValue::new(BigSteppedRangeArray { from, step, len }))
+		}
+	}
+}
+
 #[builtin]
-pub fn builtin_join(sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
+pub fn builtin_join(ctx: Context, sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
 	use std::fmt::Write;
 	Ok(match sep {
 		IndexableVal::Arr(joiner_items) => {
@@ -173,6 +656,8 @@ pub fn builtin_join(sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
 				let item = item?.clone();
 				if let Val::Arr(items) = item {
 					if !first {
+						ctx.state()
+							.check_allocation(out.len() + joiner_items.len())?;
 						out.reserve(joiner_items.len());
 						// TODO: extend
 						for item in joiner_items.iter() {
@@ -180,6 +665,7 @@ pub fn builtin_join(sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
 						}
 					}
 					first = false;
+					ctx.state().check_allocation(out.len() + items.len())?;
 					out.reserve(items.len());
 					for item in items.iter() {
 						out.push(item?);
@@ -200,6 +686,10 @@ pub fn builtin_join(sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
 			for item in arr.iter() {
 				let item = item?.clone();
 				if let Val::Str(item) = item {
+					let item = item.into_flat();
+					let sep_len = if first { 0 } else { sep.len() };
+					ctx.state()
+						.check_allocation(out.len() + sep_len + item.len())?;
 					if !first {
 						out += &sep;
 					}
@@ -218,13 +708,37 @@ pub fn builtin_join(sep: IndexableVal, arr: ArrValue) -> Result<IndexableVal> {
 }
 
 #[builtin]
-pub fn builtin_lines(arr: ArrValue) -> Result<IndexableVal> {
+pub fn builtin_lines(ctx: Context, arr: ArrValue) -> Result<IndexableVal> {
 	builtin_join(
+		ctx,
 		IndexableVal::Str("\n".into()),
 		ArrValue::extended(arr, ArrValue::eager(vec![Val::string("")])),
 	)
 }
 
+/// Joins lines with `\n`, unlike `std.lines`, no trailing newline is added
+#[builtin]
+pub fn builtin_unlines(ctx: Context, arr: ArrValue) -> Result<IndexableVal> {
+	builtin_join(ctx, IndexableVal::Str("\n".into()), arr)
+}
+
+/// Splits string on `\n` and `\r\n`, which are kept at the end of lines if `keepEnds` is set.
+///
+/// Trailing newline doesn't produce an empty last line, so `"a\nb\n"` and `"a\nb"` both result in `["a", "b"]`,
+/// and empty string results in no lines.
+#[builtin]
+#[allow(non_snake_case)]
+pub fn builtin_split_lines(str: IStr, #[default(false)] keepEnds: bool) -> ArrValue {
+	if keepEnds {
+		str.split_inclusive('\n')
+			.map(Val::string)
+			.collect::<Vec<_>>()
+	} else {
+		str.lines().map(Val::string).collect::<Vec<_>>()
+	}
+	.into()
+}
+
 #[builtin]
 pub fn builtin_resolve_path(f: String, r: String) -> String {
 	let Some(pos) = f.rfind('/') else {
@@ -254,33 +768,53 @@ pub fn builtin_deep_join(arr: IndexableVal) -> Result<String> {
 	Ok(out)
 }
 
+/// Strings are reversed by extended grapheme clusters when built with `graphemes` feature, so
+/// combining characters stay attached to their base character, and by codepoints otherwise.
 #[builtin]
-pub fn builtin_reverse(arr: ArrValue) -> ArrValue {
-	arr.reversed()
+pub fn builtin_reverse(arr: Either![IStr, ArrValue]) -> Val {
+	match arr {
+		Either2::A(s) => {
+			#[cfg(feature = "graphemes")]
+			let reversed: String = unicode_segmentation::UnicodeSegmentation::graphemes(&*s, true)
+				.rev()
+				.collect();
+			#[cfg(not(feature = "graphemes"))]
+			let reversed: String = s.chars().rev().collect();
+			Val::string(reversed)
+		}
+		Either2::B(arr) => Val::Arr(arr.reversed()),
+	}
 }
 
+/// Elements after the first `true` are not evaluated
 #[builtin]
 pub fn builtin_any(arr: ArrValue) -> Result<bool> {
-	for v in arr.iter() {
-		let v = bool::from_untyped(v?)?;
-		if v {
+	for (i, v) in arr.iter().enumerate() {
+		if bool_elem(i, v?)? {
 			return Ok(true);
 		}
 	}
 	Ok(false)
 }
 
+/// Elements after the first `false` are not evaluated
 #[builtin]
 pub fn builtin_all(arr: ArrValue) -> Result<bool> {
-	for v in arr.iter() {
-		let v = bool::from_untyped(v?)?;
-		if !v {
+	for (i, v) in arr.iter().enumerate() {
+		if !bool_elem(i, v?)? {
 			return Ok(false);
 		}
 	}
 	Ok(true)
 }
 
+fn bool_elem(i: usize, v: Val) -> Result<bool> {
+	let Val::Bool(v) = v else {
+		bail!("elem <{i}> should be a boolean, got {}", v.value_type());
+	};
+	Ok(v)
+}
+
 #[builtin]
 pub fn builtin_member(arr: IndexableVal, x: Val) -> Result<bool> {
 	match arr {
@@ -318,14 +852,25 @@ pub fn builtin_contains(arr: IndexableVal, elem: Val) -> Result<bool> {
 }
 
 #[builtin]
-pub fn builtin_count(arr: ArrValue, x: Val) -> Result<usize> {
-	let mut count = 0;
-	for item in arr.iter() {
-		if equals(&item?, &x)? {
-			count += 1;
+pub fn builtin_count(arr: IndexableVal, x: Val) -> Result<usize> {
+	match arr {
+		IndexableVal::Str(str) => {
+			let x: IStr = IStr::from_untyped(x)?;
+			if x.is_empty() {
+				return Ok(0);
+			}
+			Ok(str.matches(&*x).count())
+		}
+		IndexableVal::Arr(a) => {
+			let mut count = 0;
+			for item in a.iter() {
+				if equals(&item?, &x)? {
+					count += 1;
+				}
+			}
+			Ok(count)
 		}
 	}
-	Ok(count)
 }
 
 #[builtin]
@@ -373,24 +918,57 @@ pub fn builtin_flatten_arrays(arrs: Vec<ArrValue>) -> ArrValue {
 	flatten_inner(&arrs)
 }
 
-#[builtin]
-pub fn builtin_flatten_deep_array(value: Val) -> Result<Vec<Val>> {
-	fn process(value: Val, out: &mut Vec<Val>) -> Result<()> {
-		match value {
-			Val::Arr(arr) => {
-				for ele in arr.iter() {
-					process(ele?, out)?;
-				}
-			}
-			_ => out.push(value),
+/// Flattens nested arrays up to `depth` levels, or fully if `depth` is `None`.
+///
+/// Uses explicit stack instead of recursion, so deeply nested inputs can't overflow the native stack.
+/// Elements on the depth limit are not inspected, and are kept lazy.
+fn flatten_depth(arr: ArrValue, depth: Option<usize>) -> Result<ArrValue> {
+	let mut out = Vec::new();
+	// Arrays which are currently being flattened, with the index of their next element
+	let mut stack = vec![(arr, 0)];
+	while let Some((arr, idx)) = stack.last_mut() {
+		let Some(i) = (*idx < arr.len()).then_some(*idx) else {
+			stack.pop();
+			continue;
+		};
+		*idx += 1;
+		let arr = arr.clone();
+		if depth.is_some_and(|depth| stack.len() > depth) {
+			out.push(arr.get_lazy(i).expect("index is in bounds"));
+			continue;
+		}
+		let item = arr.get(i)?.expect("index is in bounds");
+		if let Val::Arr(inner) = item {
+			stack.push((inner, 0));
+		} else {
+			out.push(Thunk::evaluated(item));
 		}
-		Ok(())
 	}
-	let mut out = Vec::new();
-	process(value, &mut out)?;
-	Ok(out)
+	Ok(ArrValue::lazy(out))
 }
 
+#[builtin]
+pub fn builtin_flatten_deep_array(value: Val) -> Result<ArrValue> {
+	match value {
+		Val::Arr(arr) => flatten_depth(arr, None),
+		_ => Ok(ArrValue::eager(vec![value])),
+	}
+}
+
+#[builtin]
+pub fn builtin_flatten_deep(arr: ArrValue) -> Result<ArrValue> {
+	flatten_depth(arr, None)
+}
+
+/// `std.flattenDepth(arr, 1)` flattens only the directly nested arrays, `0` returns array as-is
+#[builtin]
+pub fn builtin_flatten_depth(arr: ArrValue, depth: usize) -> Result<ArrValue> {
+	flatten_depth(arr, Some(depth))
+}
+
+/// Recursively removes `null` values, empty arrays and empty objects, same as in go-jsonnet.
+///
+/// Hidden fields are removed, top-level empty array/object is returned as is.
 #[builtin]
 pub fn builtin_prune(
 	a: Val,
@@ -407,6 +985,7 @@ pub fn builtin_prune(
 			_ => true,
 		}
 	}
+	let _guard = check_depth()?;
 	Ok(match a {
 		Val::Arr(a) => {
 			let mut out = Vec::new();
@@ -451,3 +1030,183 @@ pub fn builtin_prune(
 		_ => a,
 	})
 }
+
+/// Python-like `zip`, result is truncated to the shortest array
+#[builtin]
+pub fn builtin_zip(arrays: ArrValue) -> Result<ArrValue> {
+	let mut out = Vec::with_capacity(arrays.len());
+	for (i, arr) in arrays.iter().enumerate() {
+		let arr = arr.with_description(|| format!("elem <{i}> evaluation"))?;
+		let Val::Arr(arr) = arr else {
+			bail!(
+				"zip element <{i}> should be an array, got {}",
+				arr.value_type()
+			);
+		};
+		out.push(arr);
+	}
+	Ok(ArrValue::zip(out))
+}
+
+#[builtin]
+pub fn builtin_zip_with(f: FuncVal, a: ArrValue, b: ArrValue) -> ArrValue {
+	#[derive(Trace)]
+	struct ZipWithElement {
+		f: FuncVal,
+		a: Thunk<Val>,
+		b: Thunk<Val>,
+	}
+	impl ThunkValue for ZipWithElement {
+		type Output = Val;
+
+		fn get(self: Box<Self>) -> Result<Val> {
+			self.f.evaluate_simple(&(self.a, self.b), false)
+		}
+	}
+
+	ArrValue::lazy(
+		a.iter_lazy()
+			.zip(b.iter_lazy())
+			.map(|(a, b)| Thunk::new(ZipWithElement { f: f.clone(), a, b }))
+			.collect(),
+	)
+}
+
+/// Turns `n`x`m` array of arrays into `m`x`n` one.
+///
+/// All rows should have the same length, unless `fill` is specified, in which case shorter rows are
+/// padded with it up to the length of the longest row.
+/// Output rows are computed on access, elements are not evaluated.
+#[builtin]
+pub fn builtin_transpose(matrix: ArrValue, fill: Option<Thunk<Val>>) -> Result<ArrValue> {
+	#[derive(Trace)]
+	struct TransposedRow {
+		rows: Rc<Vec<ArrValue>>,
+		column: usize,
+		fill: Option<Thunk<Val>>,
+	}
+	impl ThunkValue for TransposedRow {
+		type Output = Val;
+
+		fn get(self: Box<Self>) -> Result<Val> {
+			Ok(Val::Arr(ArrValue::lazy(
+				self.rows
+					.iter()
+					.map(|row| {
+						row.get_lazy(self.column)
+							.or_else(|| self.fill.clone())
+							.expect("rows are either of the same length, or fill is set")
+					})
+					.collect(),
+			)))
+		}
+	}
+
+	let mut rows: Vec<ArrValue> = Vec::with_capacity(matrix.len());
+	for (i, row) in matrix.iter().enumerate() {
+		let row = row.with_description(|| format!("row <{i}> evaluation"))?;
+		let Val::Arr(row) = row else {
+			bail!("row <{i}> should be an array, got {}", row.value_type());
+		};
+		if fill.is_none() {
+			if let Some(first) = rows.first() {
+				if row.len() != first.len() {
+					bail!(
+						"row <{i}> has length {}, but row <0> has length {}, specify fill to transpose ragged arrays",
+						row.len(),
+						first.len(),
+					);
+				}
+			}
+		}
+		rows.push(row);
+	}
+	let columns = rows.iter().map(ArrValue::len).max().unwrap_or(0);
+	let rows = Rc::new(rows);
+	Ok(ArrValue::lazy(
+		(0..columns)
+			.map(|column| {
+				Thunk::new(TransposedRow {
+					rows: rows.clone(),
+					column,
+					fill: fill.clone(),
+				})
+			})
+			.collect(),
+	))
+}
+
+/// Splits array into `size`-long chunks, the last chunk might be shorter.
+/// Chunks are views into the original array, elements are not copied or evaluated.
+#[builtin]
+pub fn builtin_chunk(arr: ArrValue, size: usize) -> Result<ArrValue> {
+	if size == 0 {
+		bail!("chunk size should be greater than 0");
+	}
+	let len = i32::try_from(arr.len()).map_err(|_| runtime_error!("array is too large"))?;
+	let size = i32::try_from(size).unwrap_or(i32::MAX);
+	let mut out = Vec::new();
+	let mut start = 0;
+	while start < len {
+		let end = start.saturating_add(size).min(len);
+		out.push(Val::Arr(arr.clone().slice(Some(start), Some(end), None)));
+		start = end;
+	}
+	Ok(ArrValue::eager(out))
+}
+
+/// Groups elements by the string returned from `keyF`, groups are ordered by the first occurence of the key
+#[builtin]
+pub fn builtin_group_by(arr: ArrValue, keyF: FuncVal) -> Result<ObjValue> {
+	let mut groups: Vec<(IStr, Vec<Thunk<Val>>)> = Vec::new();
+	let mut positions = HashMap::new();
+	for (i, item) in arr.iter_lazy().enumerate() {
+		let key = keyF
+			.evaluate_simple(&(item.clone(),), false)
+			.with_description(|| format!("elem <{i}> key evaluation"))?;
+		let Val::Str(key) = key else {
+			bail!(
+				"key of elem <{i}> should be a string, got {}",
+				key.value_type()
+			);
+		};
+		let key = key.into_flat();
+		let position = *positions.entry(key.clone()).or_insert_with(|| {
+			groups.push((key, Vec::new()));
+			groups.len() - 1
+		});
+		groups[position].1.push(item);
+	}
+	let mut out = ObjValueBuilder::with_capacity(groups.len());
+	for (key, items) in groups {
+		out.field(key).value(Val::Arr(ArrValue::lazy(items)));
+	}
+	Ok(out.build())
+}
+
+/// Returns `[matching, nonMatching]`, predicate is called once per element
+#[builtin]
+pub fn builtin_partition(arr: ArrValue, pred: FuncVal) -> Result<ArrValue> {
+	let mut matching = Vec::new();
+	let mut non_matching = Vec::new();
+	for (i, item) in arr.iter_lazy().enumerate() {
+		let result = pred
+			.evaluate_simple(&(item.clone(),), false)
+			.with_description(|| format!("elem <{i}> predicate evaluation"))?;
+		let Val::Bool(result) = result else {
+			bail!(
+				"predicate result for elem <{i}> should be a boolean, got {}",
+				result.value_type()
+			);
+		};
+		if result {
+			matching.push(item);
+		} else {
+			non_matching.push(item);
+		}
+	}
+	Ok(ArrValue::eager(vec![
+		Val::Arr(ArrValue::lazy(matching)),
+		Val::Arr(ArrValue::lazy(non_matching)),
+	]))
+}
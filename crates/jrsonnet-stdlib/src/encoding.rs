@@ -16,6 +16,29 @@ pub fn builtin_decode_utf8(arr: IBytes) -> Result<IStr> {
 	arr.cast_str().ok_or_else(|| runtime_error!("bad utf8"))
 }
 
+/// Hex encoding used by all hex-producing builtins
+pub fn hex_encode(bytes: &[u8], uppercase: bool) -> String {
+	const LOWER: &[u8; 16] = b"0123456789abcdef";
+	const UPPER: &[u8; 16] = b"0123456789ABCDEF";
+	let digits = if uppercase { UPPER } else { LOWER };
+	let mut out = String::with_capacity(bytes.len() * 2);
+	for byte in bytes {
+		out.push(digits[usize::from(byte >> 4)] as char);
+		out.push(digits[usize::from(byte & 0xf)] as char);
+	}
+	out
+}
+
+/// Hex-encodes utf-8 bytes of the string, or the byte array
+#[builtin]
+pub fn builtin_hex(input: Either![IStr, IBytes], #[default(false)] uppercase: bool) -> String {
+	use Either2::*;
+	match input {
+		A(s) => hex_encode(s.as_bytes(), uppercase),
+		B(b) => hex_encode(b.as_slice(), uppercase),
+	}
+}
+
 #[builtin]
 pub fn builtin_base64(input: Either![IStr, IBytes]) -> String {
 	use Either2::*;
@@ -25,19 +48,39 @@ pub fn builtin_base64(input: Either![IStr, IBytes]) -> String {
 	}
 }
 
+fn decode_base64(str: &str) -> Result<Vec<u8>> {
+	use base64::DecodeError;
+	fn symbol(byte: u8) -> String {
+		if byte.is_ascii_graphic() {
+			(byte as char).to_string()
+		} else {
+			format!("\\x{byte:02x}")
+		}
+	}
+	STANDARD.decode(str.as_bytes()).map_err(|e| match e {
+		DecodeError::InvalidByte(offset, byte) => runtime_error!(
+			"invalid base64: unexpected symbol <{}> at offset {offset}",
+			symbol(byte)
+		),
+		DecodeError::InvalidLength(len) => {
+			runtime_error!("invalid base64: {len} symbols can't form complete bytes")
+		}
+		DecodeError::InvalidLastSymbol(offset, byte) => runtime_error!(
+			"invalid base64: last symbol <{}> at offset {offset} has unused bits set",
+			symbol(byte)
+		),
+		DecodeError::InvalidPadding => runtime_error!("invalid base64: missing or invalid padding"),
+	})
+}
+
+/// Decodes base64 into the array of bytes, unlike `std.base64Decode`, doesn't require decoded data to be valid utf-8
 #[builtin]
 pub fn builtin_base64_decode_bytes(str: IStr) -> Result<IBytes> {
-	Ok(STANDARD
-		.decode(str.as_bytes())
-		.map_err(|e| runtime_error!("invalid base64: {e}"))?
-		.as_slice()
-		.into())
+	Ok(decode_base64(&str)?.as_slice().into())
 }
 
 #[builtin]
 pub fn builtin_base64_decode(str: IStr) -> Result<String> {
-	let bytes = STANDARD
-		.decode(str.as_bytes())
-		.map_err(|e| runtime_error!("invalid base64: {e}"))?;
+	let bytes = decode_base64(&str)?;
 	String::from_utf8(bytes).map_err(|_| runtime_error!("bad utf8"))
 }
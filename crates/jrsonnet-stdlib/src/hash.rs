@@ -1,30 +1,47 @@
-use jrsonnet_evaluator::{function::builtin, IStr};
+use jrsonnet_evaluator::{function::builtin, manifest::JsonFormat, IStr, Result, Val};
 
+use crate::encoding::hex_encode;
+
+/// Like all hash builtins, returns digest as lowercase hex, unless `uppercase` is set
 #[builtin]
-pub fn builtin_md5(s: IStr) -> String {
-	format!("{:x}", md5::compute(s.as_bytes()))
+pub fn builtin_md5(s: IStr, #[default(false)] uppercase: bool) -> String {
+	hex_encode(&md5::compute(s.as_bytes()).0, uppercase)
 }
 
 #[builtin]
-pub fn builtin_sha256(str: IStr) -> String {
+pub fn builtin_sha256(str: IStr, #[default(false)] uppercase: bool) -> String {
 	use sha2::digest::Digest;
-	format!("{:x}", sha2::Sha256::digest(str.as_bytes()))
+	hex_encode(&sha2::Sha256::digest(str.as_bytes()), uppercase)
 }
 
 #[builtin]
-pub fn builtin_sha512(str: IStr) -> String {
+pub fn builtin_sha512(str: IStr, #[default(false)] uppercase: bool) -> String {
 	use sha2::digest::Digest;
-	format!("{:x}", sha2::Sha512::digest(str.as_bytes()))
+	hex_encode(&sha2::Sha512::digest(str.as_bytes()), uppercase)
 }
 
 #[builtin]
-pub fn builtin_sha1(str: IStr) -> String {
+pub fn builtin_sha1(str: IStr, #[default(false)] uppercase: bool) -> String {
 	use sha1::digest::Digest;
-	format!("{:x}", sha1::Sha1::digest(str.as_bytes()))
+	hex_encode(&sha1::Sha1::digest(str.as_bytes()), uppercase)
 }
 
 #[builtin]
-pub fn builtin_sha3(str: IStr) -> String {
+pub fn builtin_sha3(str: IStr, #[default(false)] uppercase: bool) -> String {
 	use sha3::digest::Digest;
-	format!("{:x}", sha3::Sha3_512::digest(str.as_bytes()))
+	hex_encode(&sha3::Sha3_512::digest(str.as_bytes()), uppercase)
+}
+
+/// Hex-encoded sha256 of the canonical (minified, with sorted keys) JSON of the value
+#[builtin]
+pub fn builtin_hash_value(value: Val, #[default(false)] uppercase: bool) -> Result<String> {
+	use sha2::digest::Digest;
+	let canonical = value.manifest(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))?;
+	Ok(hex_encode(
+		&sha2::Sha256::digest(canonical.as_bytes()),
+		uppercase,
+	))
 }
@@ -17,7 +17,7 @@ use jrsonnet_evaluator::{
 	ContextBuilder, IStr, ObjValue, ObjValueBuilder, Thunk, Val,
 };
 use jrsonnet_gcmodule::Trace;
-use jrsonnet_parser::Source;
+use jrsonnet_parser::{Source, Span};
 pub use manifest::*;
 pub use math::*;
 pub use misc::*;
@@ -27,6 +27,7 @@ pub use parse::*;
 pub use sets::*;
 pub use sort::*;
 pub use strings::*;
+pub use time::*;
 pub use types::*;
 
 #[cfg(feature = "exp-regex")]
@@ -47,6 +48,7 @@ mod regex;
 mod sets;
 mod sort;
 mod strings;
+mod time;
 mod types;
 
 #[allow(clippy::too_many_lines)]
@@ -65,6 +67,7 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("isFunction", builtin_is_function::INST),
 		// Arrays
 		("makeArray", builtin_make_array::INST),
+		("makeArrayDeep", builtin_make_array_deep::INST),
 		("repeat", builtin_repeat::INST),
 		("slice", builtin_slice::INST),
 		("map", builtin_map::INST),
@@ -74,9 +77,16 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("filter", builtin_filter::INST),
 		("foldl", builtin_foldl::INST),
 		("foldr", builtin_foldr::INST),
+		("foldlIndexed", builtin_foldl_indexed::INST),
+		("foldrIndexed", builtin_foldr_indexed::INST),
+		("scanl", builtin_scanl::INST),
+		("scanr", builtin_scanr::INST),
 		("range", builtin_range::INST),
+		("rangeStep", builtin_range_step::INST),
 		("join", builtin_join::INST),
 		("lines", builtin_lines::INST),
+		("unlines", builtin_unlines::INST),
+		("splitLines", builtin_split_lines::INST),
 		("resolvePath", builtin_resolve_path::INST),
 		("deepJoin", builtin_deep_join::INST),
 		("reverse", builtin_reverse::INST),
@@ -91,6 +101,14 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("remove", builtin_remove::INST),
 		("flattenArrays", builtin_flatten_arrays::INST),
 		("flattenDeepArray", builtin_flatten_deep_array::INST),
+		("flattenDeep", builtin_flatten_deep::INST),
+		("flattenDepth", builtin_flatten_depth::INST),
+		("zip", builtin_zip::INST),
+		("zipWith", builtin_zip_with::INST),
+		("transpose", builtin_transpose::INST),
+		("chunk", builtin_chunk::INST),
+		("groupBy", builtin_group_by::INST),
+		("partition", builtin_partition::INST),
 		("prune", builtin_prune::INST),
 		("filterMap", builtin_filter_map::INST),
 		// Math
@@ -117,6 +135,11 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("mantissa", builtin_mantissa::INST),
 		("exponent", builtin_exponent::INST),
 		("round", builtin_round::INST),
+		("roundTo", builtin_round_to::INST),
+		("floorTo", builtin_floor_to::INST),
+		("ceilTo", builtin_ceil_to::INST),
+		("gcd", builtin_gcd::INST),
+		("lcm", builtin_lcm::INST),
 		("isEven", builtin_is_even::INST),
 		("isOdd", builtin_is_odd::INST),
 		("isInteger", builtin_is_integer::INST),
@@ -125,24 +148,32 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("mod", builtin_mod::INST),
 		("primitiveEquals", builtin_primitive_equals::INST),
 		("equals", builtin_equals::INST),
+		("deepEqual", builtin_deep_equal::INST),
 		("xor", builtin_xor::INST),
 		("xnor", builtin_xnor::INST),
 		("format", builtin_format::INST),
 		// Sort
 		("sort", builtin_sort::INST),
+		("sortBy", builtin_sort_by::INST),
+		("sortMulti", builtin_sort_multi::INST),
 		("uniq", builtin_uniq::INST),
+		("uniqBy", builtin_uniq_by::INST),
 		("set", builtin_set::INST),
 		("minArray", builtin_min_array::INST),
 		("maxArray", builtin_max_array::INST),
+		("minBy", builtin_min_by::INST),
+		("maxBy", builtin_max_by::INST),
 		// Hash
 		("md5", builtin_md5::INST),
 		("sha1", builtin_sha1::INST),
 		("sha256", builtin_sha256::INST),
 		("sha512", builtin_sha512::INST),
 		("sha3", builtin_sha3::INST),
+		("hashValue", builtin_hash_value::INST),
 		// Encoding
 		("encodeUTF8", builtin_encode_utf8::INST),
 		("decodeUTF8", builtin_decode_utf8::INST),
+		("hex", builtin_hex::INST),
 		("base64", builtin_base64::INST),
 		("base64Decode", builtin_base64_decode::INST),
 		("base64DecodeBytes", builtin_base64_decode_bytes::INST),
@@ -150,6 +181,7 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("objectFieldsEx", builtin_object_fields_ex::INST),
 		("objectFields", builtin_object_fields::INST),
 		("objectFieldsAll", builtin_object_fields_all::INST),
+		("objectFieldsInfo", builtin_object_fields_info::INST),
 		("objectValues", builtin_object_values::INST),
 		("objectValuesAll", builtin_object_values_all::INST),
 		("objectKeysValues", builtin_object_keys_values::INST),
@@ -158,6 +190,12 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("objectHas", builtin_object_has::INST),
 		("objectHasAll", builtin_object_has_all::INST),
 		("objectRemoveKey", builtin_object_remove_key::INST),
+		("objectRemoveKeys", builtin_object_remove_keys::INST),
+		("filterFields", builtin_filter_fields::INST),
+		("objectMap", builtin_object_map::INST),
+		("objectMapWithKey", builtin_object_map_with_key::INST),
+		("objectFilter", builtin_object_filter::INST),
+		("objectFilterMap", builtin_object_filter_map::INST),
 		// Manifest
 		("escapeStringJson", builtin_escape_string_json::INST),
 		("escapeStringPython", builtin_escape_string_python::INST),
@@ -176,7 +214,12 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("manifestIni", builtin_manifest_ini::INST),
 		// Parse
 		("parseJson", builtin_parse_json::INST),
+		("parseJsonStrict", builtin_parse_json_strict::INST),
+		#[cfg(feature = "yaml")]
 		("parseYaml", builtin_parse_yaml::INST),
+		#[cfg(feature = "toml")]
+		("parseToml", builtin_parse_toml::INST),
+		("parseKeyValues", builtin_parse_key_values::INST),
 		// Strings
 		("codepoint", builtin_codepoint::INST),
 		("substr", builtin_substr::INST),
@@ -191,6 +234,9 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		("split", builtin_split::INST),
 		("asciiUpper", builtin_ascii_upper::INST),
 		("asciiLower", builtin_ascii_lower::INST),
+		("toUpper", builtin_to_upper::INST),
+		("toLower", builtin_to_lower::INST),
+		("indent", builtin_indent::INST),
 		("findSubstr", builtin_find_substr::INST),
 		("parseInt", builtin_parse_int::INST),
 		#[cfg(feature = "exp-bigint")]
@@ -204,15 +250,27 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		// Misc
 		("length", builtin_length::INST),
 		("get", builtin_get::INST),
+		("getPath", builtin_get_path::INST),
+		("objectHasPath", builtin_object_has_path::INST),
 		("startsWith", builtin_starts_with::INST),
 		("endsWith", builtin_ends_with::INST),
+		("capitalize", builtin_capitalize::INST),
+		("titleCase", builtin_title_case::INST),
 		("assertEqual", builtin_assert_equal::INST),
+		("withContext", builtin_with_context::INST),
+		("try", builtin_try::INST),
 		("mergePatch", builtin_merge_patch::INST),
+		("deepMerge", builtin_deep_merge::INST),
+		("coalesce", builtin_coalesce::INST),
 		// Sets
 		("setMember", builtin_set_member::INST),
 		("setInter", builtin_set_inter::INST),
 		("setDiff", builtin_set_diff::INST),
 		("setUnion", builtin_set_union::INST),
+		// Time
+		("strftime", builtin_strftime::INST),
+		#[cfg(feature = "exp-impure-now")]
+		("now", builtin_now::INST),
 		// Regex
 		#[cfg(feature = "exp-regex")]
 		("regexQuoteMeta", builtin_regex_quote_meta::INST),
@@ -239,6 +297,18 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 			settings: settings.clone(),
 		},
 	);
+	builder.method(
+		"extVarJson",
+		builtin_ext_var_json {
+			settings: settings.clone(),
+		},
+	);
+	builder.method(
+		"extVars",
+		builtin_ext_vars {
+			settings: settings.clone(),
+		},
+	);
 	builder.method(
 		"native",
 		builtin_native {
@@ -258,6 +328,13 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 				cache: regex_cache.clone(),
 			},
 		);
+		// Alias of regexPartialMatch
+		builder.method(
+			"regexMatch",
+			builtin_regex_partial_match {
+				cache: regex_cache.clone(),
+			},
+		);
 		builder.method(
 			"regexPartialMatch",
 			builtin_regex_partial_match {
@@ -272,8 +349,18 @@ pub fn stdlib_uncached(settings: Rc<RefCell<Settings>>) -> ObjValue {
 		);
 		builder.method(
 			"regexGlobalReplace",
-			builtin_regex_global_replace { cache: regex_cache },
+			builtin_regex_global_replace {
+				cache: regex_cache.clone(),
+			},
 		);
+		// Alias of regexGlobalReplace
+		builder.method(
+			"regexReplaceAll",
+			builtin_regex_global_replace {
+				cache: regex_cache.clone(),
+			},
+		);
+		builder.method("splitRegex", builtin_split_regex { cache: regex_cache });
 	};
 
 	builder.build()
@@ -298,10 +385,7 @@ impl TracePrinter for StdTracePrinter {
 			let locs = loc.0.map_source_locations(&[loc.1]);
 			eprint!(
 				" {}:{}",
-				loc.0.source_path().path().map_or_else(
-					|| loc.0.source_path().to_string(),
-					|p| self.resolver.resolve(p)
-				),
+				display_source(&self.resolver, &loc.0),
 				locs[0].line
 			);
 		}
@@ -309,13 +393,66 @@ impl TracePrinter for StdTracePrinter {
 	}
 }
 
+/// Trace printer, which stores traces instead of printing them, to be inspected by embedder
+///
+/// Clones share the same trace storage, so one clone may be installed to [`Settings::trace_printer`],
+/// while another is kept for reading traces.
+#[derive(Clone, Default)]
+pub struct CollectingTracePrinter {
+	traces: Rc<RefCell<Vec<CollectedTrace>>>,
+}
+#[derive(Clone, Debug)]
+pub struct CollectedTrace {
+	/// Location of `std.trace` call, if known
+	pub location: Option<Span>,
+	pub message: IStr,
+}
+impl CollectingTracePrinter {
+	pub fn traces(&self) -> Ref<Vec<CollectedTrace>> {
+		self.traces.borrow()
+	}
+	pub fn take(&self) -> Vec<CollectedTrace> {
+		std::mem::take(&mut self.traces.borrow_mut())
+	}
+}
+impl TracePrinter for CollectingTracePrinter {
+	fn print_trace(&self, loc: CallLocation, value: IStr) {
+		self.traces.borrow_mut().push(CollectedTrace {
+			location: loc.0.cloned(),
+			message: value,
+		});
+	}
+}
+
+fn display_source(resolver: &PathResolver, source: &Source) -> String {
+	source
+		.source_path()
+		.path()
+		.map_or_else(|| source.source_path().to_string(), |p| resolver.resolve(p))
+}
+
+/// Formats span start as `file:line:col`
+pub(crate) fn format_trace_location(resolver: &PathResolver, span: &Span) -> String {
+	let locs = span.0.map_source_locations(&[span.1]);
+	format!(
+		"{}:{}:{}",
+		display_source(resolver, &span.0),
+		locs[0].line,
+		locs[0].column - 1
+	)
+}
+
 pub struct Settings {
 	/// Used for `std.extVar`
 	pub ext_vars: HashMap<IStr, TlaArg>,
 	/// Used for `std.native`
 	pub ext_natives: HashMap<IStr, FuncVal>,
+	/// Make `std.native` fail for unregistered names, instead of returning `null`
+	pub strict_native: bool,
 	/// Used for `std.trace`
 	pub trace_printer: Box<dyn TracePrinter>,
+	/// Prefix `std.trace` messages with `file:line:col` of the call
+	pub trace_with_location: bool,
 	/// Used for `std.thisFile`
 	pub path_resolver: PathResolver,
 }
@@ -336,7 +473,9 @@ impl ContextInitializer {
 		let settings = Settings {
 			ext_vars: HashMap::new(),
 			ext_natives: HashMap::new(),
+			strict_native: false,
 			trace_printer: Box::new(StdTracePrinter::new(resolver.clone())),
+			trace_with_location: false,
 			path_resolver: resolver,
 		};
 		let settings = Rc::new(RefCell::new(settings));
@@ -0,0 +1,56 @@
+use jrsonnet_evaluator::{
+	manifest::{ManifestFormat, SizeBudget},
+	Result, Val,
+};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::hex_encode;
+
+const FOOTER_PREFIX: &str = "\n// sha256: ";
+
+/// Appends a JSONC-style comment line with the sha256 of the generated content, i.e
+/// ```text
+/// {"a": 1}
+/// // sha256: f9d86028c6e0d64e225186f96acb69338b2c59764df79162107f5c4bb34d1310
+/// ```
+///
+/// Hash covers everything produced by the inner format, excluding the newline before the footer,
+/// use [`verify_hash_footer`] to check if the output was modified after generation.
+pub struct HashFooterFormat<I> {
+	inner: I,
+}
+impl<I> HashFooterFormat<I> {
+	pub fn new(inner: I) -> Self {
+		Self { inner }
+	}
+}
+impl<I: ManifestFormat> ManifestFormat for HashFooterFormat<I> {
+	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
+		let start = buf.len();
+		self.inner.manifest_buf(val, buf)?;
+		write_footer(start, buf);
+		Ok(())
+	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		let start = buf.len();
+		self.inner.manifest_buf_limited(val, buf, budget)?;
+		write_footer(start, buf);
+		budget.check(buf)
+	}
+}
+
+/// Appends the footer for everything written to `buf` after `start`
+fn write_footer(start: usize, buf: &mut String) {
+	let hash = Sha256::digest(&buf.as_bytes()[start..]);
+	buf.push_str(FOOTER_PREFIX);
+	buf.push_str(&hex_encode(&hash, false));
+}
+
+/// Checks content produced by [`HashFooterFormat`], trailing newline after the footer is allowed.
+///
+/// Returns `None` if there is no footer
+pub fn verify_hash_footer(content: &str) -> Option<bool> {
+	let content = content.strip_suffix('\n').unwrap_or(content);
+	let (body, hash) = content.rsplit_once(FOOTER_PREFIX)?;
+	Some(hex_encode(&Sha256::digest(body.as_bytes()), false) == hash)
+}
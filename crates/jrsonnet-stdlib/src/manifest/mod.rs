@@ -1,9 +1,11 @@
+mod hash_footer;
 mod ini;
 mod python;
 mod toml;
 mod xml;
 mod yaml;
 
+pub use hash_footer::{verify_hash_footer, HashFooterFormat};
 pub use ini::IniFormat;
 use jrsonnet_evaluator::{
 	function::builtin,
@@ -25,12 +27,15 @@ pub fn builtin_escape_string_python(str: IStr) -> Result<String> {
 	Ok(escape_string_json(&str))
 }
 
+/// With `trailing_newline`, output is terminated by `newline`, i.e `std.manifestJsonEx(v, '  ', '\r\n', trailing_newline=true)`
+/// produces CRLF-terminated file contents.
 #[builtin]
 pub fn builtin_manifest_json_ex(
 	value: Val,
 	indent: String,
 	newline: Option<IStr>,
 	key_val_sep: Option<IStr>,
+	#[default(false)] trailing_newline: bool,
 
 	#[default(false)]
 	#[cfg(feature = "exp-preserve-order")]
@@ -38,13 +43,17 @@ pub fn builtin_manifest_json_ex(
 ) -> Result<String> {
 	let newline = newline.as_deref().unwrap_or("\n");
 	let key_val_sep = key_val_sep.as_deref().unwrap_or(": ");
-	value.manifest(JsonFormat::std_to_json(
+	let mut out = value.manifest(JsonFormat::std_to_json(
 		indent,
 		newline,
 		key_val_sep,
 		#[cfg(feature = "exp-preserve-order")]
 		preserve_order,
-	))
+	))?;
+	if trailing_newline {
+		out.push_str(newline);
+	}
+	Ok(out)
 }
 
 #[builtin]
@@ -60,6 +69,7 @@ pub fn builtin_manifest_json(
 		"    ".to_owned(),
 		None,
 		None,
+		false,
 		#[cfg(feature = "exp-preserve-order")]
 		preserve_order,
 	)
@@ -98,25 +108,30 @@ pub fn builtin_manifest_yaml_doc(
 }
 
 #[builtin]
+#[allow(clippy::fn_params_excessive_bools)]
 pub fn builtin_manifest_yaml_stream(
 	value: Val,
 	#[default(false)] indent_array_in_object: bool,
 	#[default(true)] c_document_end: bool,
 	#[default(true)] quote_keys: bool,
+	#[default(true)] leading_separator: bool,
 
 	#[default(false)]
 	#[cfg(feature = "exp-preserve-order")]
 	preserve_order: bool,
 ) -> Result<String> {
-	value.manifest(YamlStreamFormat::std_yaml_stream(
-		YamlFormat::std_to_yaml(
-			indent_array_in_object,
-			quote_keys,
-			#[cfg(feature = "exp-preserve-order")]
-			preserve_order,
-		),
-		c_document_end,
-	))
+	value.manifest(
+		YamlStreamFormat::std_yaml_stream(
+			YamlFormat::std_to_yaml(
+				indent_array_in_object,
+				quote_keys,
+				#[cfg(feature = "exp-preserve-order")]
+				preserve_order,
+			),
+			c_document_end,
+		)
+		.with_leading_separator(leading_separator),
+	)
 }
 
 #[builtin]
@@ -2,7 +2,7 @@ use std::{borrow::Cow, fmt::Write};
 
 use jrsonnet_evaluator::{
 	bail, in_description_frame,
-	manifest::{escape_string_json_buf, ManifestFormat},
+	manifest::{escape_string_json_buf, ManifestFormat, SizeBudget},
 	Result, ResultExt, Val,
 };
 
@@ -63,7 +63,10 @@ impl YamlFormat<'_> {
 }
 impl ManifestFormat for YamlFormat<'_> {
 	fn manifest_buf(&self, val: Val, buf: &mut String) -> Result<()> {
-		manifest_yaml_ex_buf(&val, buf, &mut String::new(), self)
+		manifest_yaml_ex_buf(&val, buf, &mut String::new(), self, None)
+	}
+	fn manifest_buf_limited(&self, val: Val, buf: &mut String, budget: SizeBudget) -> Result<()> {
+		manifest_yaml_ex_buf(&val, buf, &mut String::new(), self, Some(budget))
 	}
 }
 
@@ -102,16 +105,18 @@ fn yaml_needs_quotes(string: &str) -> bool {
 #[allow(dead_code)]
 fn manifest_yaml_ex(val: &Val, options: &YamlFormat<'_>) -> Result<String> {
 	let mut out = String::new();
-	manifest_yaml_ex_buf(val, &mut out, &mut String::new(), options)?;
+	manifest_yaml_ex_buf(val, &mut out, &mut String::new(), options, None)?;
 	Ok(out)
 }
 
+/// `budget` is checked after every written value
 #[allow(clippy::too_many_lines)]
 fn manifest_yaml_ex_buf(
 	val: &Val,
 	buf: &mut String,
 	cur_padding: &mut String,
 	options: &YamlFormat<'_>,
+	budget: Option<SizeBudget>,
 ) -> Result<()> {
 	match val {
 		Val::Bool(v) => {
@@ -180,7 +185,7 @@ fn manifest_yaml_ex_buf(
 				}
 				in_description_frame(
 					|| format!("elem <{i}> manifestification"),
-					|| manifest_yaml_ex_buf(&item, buf, cur_padding, options),
+					|| manifest_yaml_ex_buf(&item, buf, cur_padding, options, budget),
 				)?;
 				cur_padding.truncate(prev_len);
 			}
@@ -227,7 +232,7 @@ fn manifest_yaml_ex_buf(
 				}
 				in_description_frame(
 					|| format!("field <{key}> manifestification"),
-					|| manifest_yaml_ex_buf(&value, buf, cur_padding, options),
+					|| manifest_yaml_ex_buf(&value, buf, cur_padding, options, budget),
 				)?;
 				cur_padding.truncate(prev_len);
 			}
@@ -237,5 +242,5 @@ fn manifest_yaml_ex_buf(
 		}
 		Val::Func(_) => bail!("tried to manifest function"),
 	}
-	Ok(())
+	budget.map_or(Ok(()), |budget| budget.check(buf))
 }
@@ -1,4 +1,14 @@
-use jrsonnet_evaluator::{function::builtin, typed::PositiveF64};
+use std::cmp::Ordering;
+
+use jrsonnet_evaluator::{
+	bail,
+	function::builtin,
+	operator::evaluate_compare_op,
+	typed::{PositiveF64, Typed},
+	val::{ArrValue, Val},
+	Result,
+};
+use jrsonnet_parser::BinaryOpType;
 
 #[builtin]
 pub fn builtin_abs(n: f64) -> f64 {
@@ -6,12 +16,17 @@ pub fn builtin_abs(n: f64) -> f64 {
 }
 
 #[builtin]
-pub fn builtin_sign(n: f64) -> f64 {
-	if n == 0. {
-		0.
-	} else {
-		n.signum()
+pub fn builtin_sign(n: Val) -> Result<f64> {
+	#[cfg(feature = "exp-bigint")]
+	if let Val::BigInt(n) = &n {
+		return Ok(match n.sign() {
+			num_bigint::Sign::Minus => -1.,
+			num_bigint::Sign::NoSign => 0.,
+			num_bigint::Sign::Plus => 1.,
+		});
 	}
+	let n = f64::from_untyped(n)?;
+	Ok(if n == 0. { 0. } else { n.signum() })
 }
 
 #[builtin]
@@ -24,15 +39,65 @@ pub fn builtin_min(a: f64, b: f64) -> f64 {
 	a.min(b)
 }
 
+/// Jsonnet numbers are always finite (operations producing NaN fail), so there is no special NaN handling.
+///
+/// With `exp-bigint`, bigints can be clamped by bigint bounds.
 #[allow(non_snake_case)]
 #[builtin]
-pub fn builtin_clamp(x: f64, minVal: f64, maxVal: f64) -> f64 {
-	x.clamp(minVal, maxVal)
+pub fn builtin_clamp(x: Val, minVal: Val, maxVal: Val) -> Result<Val> {
+	for v in [&x, &minVal, &maxVal] {
+		ensure_numeric(v)?;
+	}
+	if evaluate_compare_op(&minVal, &maxVal, BinaryOpType::Gt)? == Ordering::Greater {
+		bail!("clamp minVal should not be greater than maxVal");
+	}
+	Ok(
+		if evaluate_compare_op(&x, &minVal, BinaryOpType::Lt)? == Ordering::Less {
+			minVal
+		} else if evaluate_compare_op(&x, &maxVal, BinaryOpType::Gt)? == Ordering::Greater {
+			maxVal
+		} else {
+			x
+		},
+	)
+}
+
+fn ensure_numeric(v: &Val) -> Result<()> {
+	#[cfg(feature = "exp-bigint")]
+	if matches!(v, Val::BigInt(_)) {
+		return Ok(());
+	}
+	f64::from_untyped(v.clone())?;
+	Ok(())
 }
 
+/// With `exp-bigint`, array of bigints is summed exactly, mixing bigints with numbers is an error
 #[builtin]
-pub fn builtin_sum(arr: Vec<f64>) -> f64 {
-	arr.iter().sum()
+pub fn builtin_sum(arr: ArrValue) -> Result<Val> {
+	let mut sum = 0.0;
+	#[cfg(feature = "exp-bigint")]
+	let mut big_sum: Option<num_bigint::BigInt> = None;
+	for (i, v) in arr.iter().enumerate() {
+		match v? {
+			#[cfg(feature = "exp-bigint")]
+			Val::Num(_) if big_sum.is_some() => {
+				bail!("elem <{i}> is a number, but the previous elements are bigints")
+			}
+			Val::Num(n) => sum += n.get(),
+			#[cfg(feature = "exp-bigint")]
+			Val::BigInt(_) if i != 0 && big_sum.is_none() => {
+				bail!("elem <{i}> is a bigint, but the previous elements are numbers")
+			}
+			#[cfg(feature = "exp-bigint")]
+			Val::BigInt(n) => *big_sum.get_or_insert_with(Default::default) += *n,
+			v => bail!("elem <{i}> should be a number, got {}", v.value_type()),
+		}
+	}
+	#[cfg(feature = "exp-bigint")]
+	if let Some(big_sum) = big_sum {
+		return Ok(Val::BigInt(Box::new(big_sum)));
+	}
+	Ok(Val::try_num(sum)?)
 }
 
 #[builtin]
@@ -131,6 +196,189 @@ pub fn builtin_round(x: f64) -> f64 {
 	x.round()
 }
 
+#[derive(Clone, Copy)]
+enum DecimalRounding {
+	/// Half away from zero
+	Round,
+	Floor,
+	Ceil,
+}
+
+/// Rounds `x` to `digits` decimal places, operating on the shortest decimal representation of `x`,
+/// so that `2.675` is rounded to `2.68`, even though it is stored as `2.67499999...`
+fn round_decimal(x: f64, digits: i32, mode: DecimalRounding) -> Result<Val> {
+	if x == 0.0 {
+		return Ok(Val::Num(0.0.try_into()?));
+	}
+	let negative = x < 0.0;
+	let repr = format!("{:e}", x.abs());
+	let (mantissa, exp) = repr.split_once('e').expect("exponent is always present");
+	let exp: i64 = exp.parse().expect("exponent is an integer");
+	let significant = mantissa.replace('.', "");
+
+	// Number of significant digits which are kept, may be negative if all of them are dropped
+	let keep = exp + 1 + i64::from(digits);
+	if keep >= significant.len() as i64 {
+		return Ok(Val::Num(x.try_into()?));
+	}
+	let (kept, first_dropped) = match keep.cmp(&0) {
+		Ordering::Greater => {
+			#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+			let (kept, dropped) = significant.split_at(keep as usize);
+			(kept.to_owned(), dropped.as_bytes()[0])
+		}
+		Ordering::Equal => (String::new(), significant.as_bytes()[0]),
+		Ordering::Less => (String::new(), b'0'),
+	};
+	// Dropped part is never zero, as the shortest representation has no trailing zeroes
+	let round_up = match mode {
+		DecimalRounding::Round => first_dropped >= b'5',
+		DecimalRounding::Floor => negative,
+		DecimalRounding::Ceil => !negative,
+	};
+
+	let mut kept = kept.into_bytes();
+	if round_up {
+		let mut carry = true;
+		for d in kept.iter_mut().rev() {
+			if *d == b'9' {
+				*d = b'0';
+			} else {
+				*d += 1;
+				carry = false;
+				break;
+			}
+		}
+		if carry {
+			kept.insert(0, b'1');
+		}
+	}
+	if kept.iter().all(|d| *d == b'0') {
+		return Ok(Val::Num(0.0.try_into()?));
+	}
+	let kept = String::from_utf8(kept).expect("digits are ascii");
+	let sign = if negative { "-" } else { "" };
+	let rounded: f64 = format!("{sign}{kept}e{}", -i64::from(digits))
+		.parse()
+		.expect("valid float");
+	Ok(Val::try_num(rounded)?)
+}
+
+/// Rounds half away from zero to `digits` decimal places, negative `digits` round to tens, hundreds, etc.
+///
+/// Rounding is performed on the shortest decimal representation of the number,
+/// so `std.roundTo(2.675, 2)` is `2.68`, despite `2.675` not being exactly representable as binary float.
+#[builtin]
+pub fn builtin_round_to(x: f64, digits: i32) -> Result<Val> {
+	round_decimal(x, digits, DecimalRounding::Round)
+}
+
+/// Same as `roundTo`, but rounds toward negative infinity
+#[builtin]
+pub fn builtin_floor_to(x: f64, digits: i32) -> Result<Val> {
+	round_decimal(x, digits, DecimalRounding::Floor)
+}
+
+/// Same as `roundTo`, but rounds toward positive infinity
+#[builtin]
+pub fn builtin_ceil_to(x: f64, digits: i32) -> Result<Val> {
+	round_decimal(x, digits, DecimalRounding::Ceil)
+}
+
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+enum Integer {
+	Small(u64),
+	#[cfg(feature = "exp-bigint")]
+	Big(num_bigint::BigInt),
+}
+impl Integer {
+	/// Sign is dropped, as `gcd`/`lcm` results don't depend on it
+	#[allow(clippy::float_cmp)]
+	fn from_val(v: Val) -> Result<Self> {
+		#[cfg(feature = "exp-bigint")]
+		if let Val::BigInt(v) = v {
+			return Ok(Self::Big(num_bigint::BigInt::from(v.magnitude().clone())));
+		}
+		let v = f64::from_untyped(v)?;
+		if v.trunc() != v {
+			bail!("expected integer, got {v}");
+		}
+		if v.abs() > MAX_SAFE_INTEGER {
+			bail!("integer {v:e} is outside of safe range");
+		}
+		#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+		Ok(Self::Small(v.abs() as u64))
+	}
+	#[cfg(feature = "exp-bigint")]
+	fn into_big(self) -> num_bigint::BigInt {
+		match self {
+			Self::Small(v) => v.into(),
+			Self::Big(v) => v,
+		}
+	}
+}
+
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+	while b != 0 {
+		(a, b) = (b, a % b);
+	}
+	a
+}
+
+#[cfg(feature = "exp-bigint")]
+fn gcd_big(mut a: num_bigint::BigInt, mut b: num_bigint::BigInt) -> num_bigint::BigInt {
+	while b != num_bigint::BigInt::default() {
+		let r = &a % &b;
+		(a, b) = (b, r);
+	}
+	a
+}
+
+/// Greatest common divisor, `gcd(0, 0)` is `0`
+#[builtin]
+pub fn builtin_gcd(a: Val, b: Val) -> Result<Val> {
+	match (Integer::from_val(a)?, Integer::from_val(b)?) {
+		#[allow(clippy::cast_precision_loss)]
+		(Integer::Small(a), Integer::Small(b)) => Ok(Val::try_num(gcd_u64(a, b) as f64)?),
+		#[cfg(feature = "exp-bigint")]
+		(a, b) => Ok(Val::BigInt(Box::new(gcd_big(a.into_big(), b.into_big())))),
+	}
+}
+
+/// Least common multiple, which is `0` if any of the arguments is `0`.
+///
+/// Results outside of safe integer range are returned as bigints with `exp-bigint`, and are errors otherwise
+#[builtin]
+pub fn builtin_lcm(a: Val, b: Val) -> Result<Val> {
+	match (Integer::from_val(a)?, Integer::from_val(b)?) {
+		(Integer::Small(a), Integer::Small(b)) => {
+			if a == 0 || b == 0 {
+				return Ok(Val::Num(0.0.try_into()?));
+			}
+			let lcm = u128::from(a / gcd_u64(a, b)) * u128::from(b);
+			#[allow(clippy::cast_precision_loss)]
+			if lcm as f64 <= MAX_SAFE_INTEGER {
+				return Ok(Val::try_num(lcm as f64)?);
+			}
+			#[cfg(feature = "exp-bigint")]
+			return Ok(Val::BigInt(Box::new(lcm.into())));
+			#[cfg(not(feature = "exp-bigint"))]
+			bail!("lcm result {lcm} is outside of safe integer range");
+		}
+		#[cfg(feature = "exp-bigint")]
+		(a, b) => {
+			let (a, b) = (a.into_big(), b.into_big());
+			let zero = num_bigint::BigInt::default();
+			if a == zero || b == zero {
+				return Ok(Val::BigInt(Box::new(zero)));
+			}
+			let gcd = gcd_big(a.clone(), b.clone());
+			Ok(Val::BigInt(Box::new(a / gcd * b)))
+		}
+	}
+}
+
 #[builtin]
 pub fn builtin_is_even(x: f64) -> bool {
 	builtin_round(x) % 2.0 == 0.0
@@ -1,25 +1,62 @@
-use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
 use jrsonnet_evaluator::{
 	bail,
 	error::{ErrorKind::*, Result},
 	function::{builtin, ArgLike, CallLocation, FuncVal},
+	in_frame,
 	manifest::JsonFormat,
-	typed::{Either2, Either4},
-	val::{equals, ArrValue},
+	runtime_error,
+	typed::{Either2, Either3, Either4, Typed},
+	val::{equals, primitive_equals, ArrValue, ThunkValue},
 	Context, Either, IStr, ObjValue, ObjValueBuilder, ResultExt, Thunk, Val,
 };
+use jrsonnet_gcmodule::Trace;
 
-use crate::{extvar_source, Settings};
+use crate::{extvar_source, format_trace_location, Settings};
 
+/// For strings, `unit` may be `codepoints` (default), `bytes` (in utf-8), or `graphemes`
+/// (extended grapheme clusters, requires `graphemes` feature).
 #[builtin]
-pub fn builtin_length(x: Either![IStr, ArrValue, ObjValue, FuncVal]) -> usize {
+pub fn builtin_length(
+	x: Either![IStr, ArrValue, ObjValue, FuncVal],
+	unit: Option<IStr>,
+) -> Result<usize> {
 	use Either4::*;
-	match x {
-		A(x) => x.chars().count(),
+	if let (Some(_), B(_) | C(_) | D(_)) = (&unit, &x) {
+		bail!("length unit is only supported for strings");
+	}
+	Ok(match x {
+		A(x) => match unit.as_deref().unwrap_or("codepoints") {
+			"codepoints" => x.chars().count(),
+			"bytes" => x.len(),
+			#[cfg(feature = "graphemes")]
+			"graphemes" => unicode_segmentation::UnicodeSegmentation::graphemes(&*x, true).count(),
+			#[cfg(not(feature = "graphemes"))]
+			"graphemes" => bail!("counting graphemes requires jrsonnet to be built with graphemes feature"),
+			unit => {
+				bail!("unknown length unit <{unit}>, expected <codepoints>, <bytes> or <graphemes>")
+			}
+		},
 		B(x) => x.len(),
 		C(x) => x.len(),
 		D(f) => f.params_len(),
+	})
+}
+
+/// Whether string, array or object is empty, without computing its full length.
+///
+/// Only visible object fields are considered, unless `inc_hidden` is set.
+#[builtin]
+pub fn builtin_is_empty(
+	str: Either![IStr, ArrValue, ObjValue],
+	#[default(false)] inc_hidden: bool,
+) -> bool {
+	use Either3::*;
+	match str {
+		A(s) => s.is_empty(),
+		B(a) => a.is_empty(),
+		C(o) => o.is_empty_ex(inc_hidden),
 	}
 }
 
@@ -46,12 +83,122 @@ pub fn builtin_get(
 	Ok(v)
 }
 
+/// Same as `std.get`, but walks a `path` of object field names and array indices.
+///
+/// `default` is returned if any step is missing. When the step can't be applied to the current value
+/// (i.e indexing a string, or using a field name on array), `default` is returned too, unless `strict` is set.
+#[builtin]
+pub fn builtin_get_path(
+	o: ObjValue,
+	path: ArrValue,
+	default: Option<Thunk<Val>>,
+	#[default(true)] inc_hidden: bool,
+	#[default(false)] strict: bool,
+) -> Result<Val> {
+	let do_default = move || {
+		let Some(default) = default else {
+			return Ok(Val::Null);
+		};
+		default.evaluate()
+	};
+	let mut cur = Val::Obj(o);
+	for (i, step) in path.iter().enumerate() {
+		let step = step.with_description(|| format!("path element <{i}> evaluation"))?;
+		cur = match (cur, step) {
+			(Val::Obj(obj), Val::Str(key)) => {
+				let key = key.into_flat();
+				if !inc_hidden && !obj.has_field_ex(key.clone(), false) {
+					return do_default();
+				}
+				let Some(v) = obj.get(key)? else {
+					return do_default();
+				};
+				v
+			}
+			(Val::Arr(arr), Val::Num(idx)) => {
+				let idx = idx.get();
+				if idx < 0.0 || idx.fract() != 0.0 {
+					return do_default();
+				}
+				#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+				let Some(v) = arr.get(idx as usize)?
+				else {
+					return do_default();
+				};
+				v
+			}
+			(_, Val::Str(_) | Val::Num(_)) if !strict => return do_default(),
+			(cur, step @ (Val::Str(_) | Val::Num(_))) => bail!(
+				"can't index {} with {} at path element <{i}>",
+				cur.value_type(),
+				step.value_type(),
+			),
+			(_, step) => bail!(
+				"path element <{i}> should be a string or a number, got {}",
+				step.value_type(),
+			),
+		};
+	}
+	Ok(cur)
+}
+
+/// Checks if `path` of object field names and array indices exists, without evaluating the value at the end of it.
+///
+/// Intermediate values are evaluated, and if any of them can't be indexed by the next step - `false` is returned.
+#[builtin]
+pub fn builtin_object_has_path(
+	o: ObjValue,
+	path: ArrValue,
+	#[default(true)] inc_hidden: bool,
+) -> Result<bool> {
+	let mut cur = Val::Obj(o);
+	let len = path.len();
+	for (i, step) in path.iter().enumerate() {
+		let step = step.with_description(|| format!("path element <{i}> evaluation"))?;
+		let last = i + 1 == len;
+		cur = match (cur, step) {
+			(Val::Obj(obj), Val::Str(key)) => {
+				let key = key.into_flat();
+				if !obj.has_field_ex(key.clone(), inc_hidden) {
+					return Ok(false);
+				}
+				if last {
+					return Ok(true);
+				}
+				obj.get(key)?.expect("field exists")
+			}
+			(Val::Arr(arr), Val::Num(idx)) => {
+				let idx = idx.get();
+				#[allow(clippy::cast_precision_loss)]
+				if idx < 0.0 || idx.fract() != 0.0 || idx >= arr.len() as f64 {
+					return Ok(false);
+				}
+				if last {
+					return Ok(true);
+				}
+				#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+				arr.get(idx as usize)?.expect("index is in bounds")
+			}
+			(_, Val::Str(_) | Val::Num(_)) => return Ok(false),
+			(_, step) => bail!(
+				"path element <{i}> should be a string or a number, got {}",
+				step.value_type(),
+			),
+		};
+	}
+	Ok(true)
+}
+
 #[builtin(fields(
 	settings: Rc<RefCell<Settings>>,
 ))]
 pub fn builtin_ext_var(this: &builtin_ext_var, ctx: Context, x: IStr) -> Result<Val> {
+	evaluate_ext_var(&this.settings, &ctx, x)
+}
+
+fn evaluate_ext_var(settings: &RefCell<Settings>, ctx: &Context, x: IStr) -> Result<Val> {
 	let ctx = ctx.state().create_default_context(extvar_source(&x, ""));
-	this.settings
+	settings
 		.borrow()
 		.ext_vars
 		.get(&x)
@@ -61,16 +208,49 @@ pub fn builtin_ext_var(this: &builtin_ext_var, ctx: Context, x: IStr) -> Result<
 		.evaluate()
 }
 
+/// Same as `std.parseJson(std.extVar(x))`, but errors mention the variable name
 #[builtin(fields(
 	settings: Rc<RefCell<Settings>>,
 ))]
-pub fn builtin_native(this: &builtin_native, x: IStr) -> Val {
-	this.settings
-		.borrow()
-		.ext_natives
-		.get(&x)
-		.cloned()
-		.map_or(Val::Null, Val::Func)
+pub fn builtin_ext_var_json(this: &builtin_ext_var_json, ctx: Context, x: IStr) -> Result<Val> {
+	let value = evaluate_ext_var(&this.settings, &ctx, x.clone())?;
+	let Val::Str(value) = value else {
+		bail!(
+			"external variable <{x}> should be a string, got {}",
+			value.value_type()
+		);
+	};
+	serde_json::from_str(&value.into_flat())
+		.map_err(|e| runtime_error!("failed to parse external variable <{x}> as json: {e}"))
+}
+
+/// Object of all defined external variables, values are evaluated lazily, the same way as with `std.extVar`
+#[builtin(fields(
+	settings: Rc<RefCell<Settings>>,
+))]
+pub fn builtin_ext_vars(this: &builtin_ext_vars, ctx: Context) -> Result<ObjValue> {
+	let state = ctx.state();
+	let mut out = ObjValueBuilder::new();
+	for (name, value) in &this.settings.borrow().ext_vars {
+		let ctx = state.create_default_context(extvar_source(name, ""));
+		out.field(name.clone())
+			.thunk(value.evaluate_arg(ctx, false)?)?;
+	}
+	Ok(out.build())
+}
+
+#[builtin(fields(
+	settings: Rc<RefCell<Settings>>,
+))]
+pub fn builtin_native(this: &builtin_native, x: IStr) -> Result<Val> {
+	let settings = this.settings.borrow();
+	if let Some(native) = settings.ext_natives.get(&x) {
+		return Ok(Val::Func(native.clone()));
+	}
+	if settings.strict_native {
+		bail!("unknown native function '{x}'");
+	}
+	Ok(Val::Null)
 }
 
 #[builtin(fields(
@@ -82,17 +262,71 @@ pub fn builtin_trace(
 	str: Val,
 	rest: Option<Thunk<Val>>,
 ) -> Result<Val> {
-	this.settings.borrow().trace_printer.print_trace(
-		loc,
-		match &str {
-			Val::Str(s) => s.clone().into_flat(),
-			Val::Func(f) => format!("{f:?}").into(),
-			v => v.manifest(JsonFormat::debug())?.into(),
-		},
-	);
+	let message: IStr = match &str {
+		Val::Str(s) => s.clone().into_flat(),
+		Val::Func(f) => format!("{f:?}").into(),
+		v => v.manifest(JsonFormat::debug())?.into(),
+	};
+	let settings = this.settings.borrow();
+	let message = match loc.0 {
+		Some(span) if settings.trace_with_location => format!(
+			"{}: {message}",
+			format_trace_location(&settings.path_resolver, span)
+		)
+		.into(),
+		_ => message,
+	};
+	settings.trace_printer.print_trace(loc, message);
 	rest.map_or_else(|| Ok(str), |rest| rest.evaluate())
 }
 
+/// First element of `values` which is not `null`, or `null` if there is none.
+///
+/// Elements are evaluated in order, and ones after the result are never evaluated.
+#[builtin]
+pub fn builtin_coalesce(values: ArrValue) -> Result<Val> {
+	for (i, value) in values.iter_lazy().enumerate() {
+		let value = value
+			.evaluate()
+			.with_description(|| format!("elem <{i}> evaluation"))?;
+		if !matches!(value, Val::Null) {
+			return Ok(value);
+		}
+	}
+	Ok(Val::Null)
+}
+
+/// Evaluates `body`, falling back to `fallback` if it fails.
+///
+/// Only `body` itself is evaluated, errors from lazy fields/elements of the result are not caught.
+/// Syntax errors in imported files, and exceeded evaluation limits (stack depth, steps, timeout,
+/// allocation budget) are always propagated.
+#[builtin]
+pub fn builtin_try(body: Thunk<Val>, fallback: Thunk<Val>) -> Result<Val> {
+	match body.evaluate() {
+		Err(e)
+			if !matches!(
+				e.error(),
+				ImportSyntaxError { .. }
+					| StackOverflow | EvaluationStepLimitExceeded
+					| EvaluationTimeout | AllocationBudgetExceeded
+			) =>
+		{
+			fallback.evaluate()
+		}
+		v => v,
+	}
+}
+
+/// Evaluates `body`, adding `desc` frame to the trace of any error it raises
+///
+/// Only `body` itself is evaluated in the frame, errors from lazy fields/elements of the result
+/// are reported without it.
+#[builtin]
+pub fn builtin_with_context(loc: CallLocation, desc: IStr, body: Thunk<Val>) -> Result<Val> {
+	in_frame(loc, || desc.to_string(), || body.evaluate())
+}
+
 #[allow(clippy::comparison_chain)]
 #[builtin]
 pub fn builtin_starts_with(a: Either![IStr, ArrValue], b: Either![IStr, ArrValue]) -> Result<bool> {
@@ -142,11 +376,138 @@ pub fn builtin_ends_with(a: Either![IStr, ArrValue], b: Either![IStr, ArrValue])
 	})
 }
 
+#[builtin]
+pub fn builtin_capitalize(str: IStr) -> IStr {
+	let mut chars = str.chars();
+	let Some(first) = chars.next() else {
+		return str;
+	};
+	let mut out = String::with_capacity(str.len());
+	out.extend(first.to_uppercase());
+	out.extend(chars.flat_map(char::to_lowercase));
+	out.into()
+}
+
+/// Uppercases first letter of every word, rest of the word is kept as-is.
+/// Words are delimited by whitespace, or by any of the `delimiters` chars, if specified.
+#[builtin]
+pub fn builtin_title_case(str: IStr, delimiters: Option<IStr>) -> IStr {
+	let is_delimiter = |c: char| {
+		delimiters
+			.as_ref()
+			.map_or_else(|| c.is_whitespace(), |d| d.contains(c))
+	};
+	let mut out = String::with_capacity(str.len());
+	let mut word_start = true;
+	for c in str.chars() {
+		if is_delimiter(c) {
+			word_start = true;
+			out.push(c);
+		} else if word_start {
+			word_start = false;
+			out.extend(c.to_uppercase());
+		} else {
+			out.push(c);
+		}
+	}
+	out.into()
+}
+
+/// First mismatch found by [`first_difference`], `None` side means the field/element is missing
+struct Difference {
+	/// Flat path to the mismatching value, i.e `.spec.ports[0]`, empty for the root
+	path: String,
+	a: Option<Val>,
+	b: Option<Val>,
+}
+
+/// Same comparison as [`equals`], but reports where the values differ, functions are never equal
+fn first_difference(a: &Val, b: &Val, path: &mut String) -> Result<Option<Difference>> {
+	let here = |a: Option<Val>, b: Option<Val>, path: &str| {
+		Ok(Some(Difference {
+			path: path.to_owned(),
+			a,
+			b,
+		}))
+	};
+	if a.value_type() != b.value_type() {
+		return here(Some(a.clone()), Some(b.clone()), path);
+	}
+	match (a, b) {
+		(Val::Arr(a), Val::Arr(b)) => {
+			if ArrValue::ptr_eq(a, b) {
+				return Ok(None);
+			}
+			for i in 0..a.len().max(b.len()) {
+				let old_len = path.len();
+				path.push_str(&format!("[{i}]"));
+				match (a.get(i)?, b.get(i)?) {
+					(Some(a), Some(b)) => {
+						if let Some(diff) = first_difference(&a, &b, path)? {
+							return Ok(Some(diff));
+						}
+					}
+					(a, b) => return here(a, b, path),
+				}
+				path.truncate(old_len);
+			}
+			Ok(None)
+		}
+		(Val::Obj(a), Val::Obj(b)) => {
+			if ObjValue::ptr_eq(a, b) {
+				return Ok(None);
+			}
+			let mut fields = a.fields(
+				#[cfg(feature = "exp-preserve-order")]
+				false,
+			);
+			fields.extend(b.fields(
+				#[cfg(feature = "exp-preserve-order")]
+				false,
+			));
+			fields.sort_unstable();
+			fields.dedup();
+			for field in fields {
+				let old_len = path.len();
+				path.push('.');
+				path.push_str(&field);
+				// Hidden fields are not compared, same as missing ones
+				let visible = |obj: &ObjValue| -> Result<Option<Val>> {
+					if obj.has_field(field.clone()) {
+						obj.get(field.clone())
+					} else {
+						Ok(None)
+					}
+				};
+				match (visible(a)?, visible(b)?) {
+					(Some(a), Some(b)) => {
+						if let Some(diff) = first_difference(&a, &b, path)? {
+							return Ok(Some(diff));
+						}
+					}
+					(a, b) => return here(a, b, path),
+				}
+				path.truncate(old_len);
+			}
+			Ok(None)
+		}
+		(Val::Func(_), Val::Func(_)) => here(Some(a.clone()), Some(b.clone()), path),
+		(a, b) => {
+			if primitive_equals(a, b)? {
+				Ok(None)
+			} else {
+				here(Some(a.clone()), Some(b.clone()), path)
+			}
+		}
+	}
+}
+
+/// On failure, reports the first differing path, with values at this path
 #[builtin]
 pub fn builtin_assert_equal(a: Val, b: Val) -> Result<bool> {
-	if equals(&a, &b)? {
+	let Some(diff) = first_difference(&a, &b, &mut String::new())? else {
 		return Ok(true);
-	}
+	};
 	// TODO: Use debug output format
 	let format = JsonFormat::std_to_json(
 		"  ".to_owned(),
@@ -155,59 +516,211 @@ pub fn builtin_assert_equal(a: Val, b: Val) -> Result<bool> {
 		#[cfg(feature = "exp-preserve-order")]
 		true,
 	);
-	let a = a.manifest(&format).description("<a> manifestification")?;
-	let b = b.manifest(&format).description("<b> manifestification")?;
-	bail!("assertion failed: A != B\nA: {a}\nB: {b}")
+	let show = |v: Option<Val>, side: &str| match v {
+		None => Ok("<missing>".to_owned()),
+		Some(Val::Func(_)) => Ok("<function>".to_owned()),
+		Some(v) => v
+			.manifest(&format)
+			.with_description(|| format!("<{side}> manifestification")),
+	};
+	let note = if matches!((&diff.a, &diff.b), (Some(Val::Func(_)), Some(Val::Func(_)))) {
+		"\nfunctions can't be compared, and are never equal"
+	} else {
+		""
+	};
+	let path = diff.path;
+	let a = show(diff.a, "a")?;
+	let b = show(diff.b, "b")?;
+	bail!("assertion failed: A{path} != B{path}\nA{path}: {a}\nB{path}: {b}{note}")
 }
 
+/// [RFC 7396](https://datatracker.ietf.org/doc/html/rfc7396) JSON Merge Patch
+///
+/// Target fields which are not mentioned in the patch are kept lazy.
+/// With `preserve_order`, target fields are kept in place, and new fields are appended in the patch order.
 #[builtin]
-pub fn builtin_merge_patch(target: Val, patch: Val) -> Result<Val> {
+pub fn builtin_merge_patch(
+	target: Val,
+	patch: Val,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<Val> {
 	let Some(patch) = patch.as_obj() else {
 		return Ok(patch);
 	};
-	let Some(target) = target.as_obj() else {
-		return Ok(Val::Obj(patch));
-	};
-	let target_fields = target
-		.fields(
-			// FIXME: Makes no sense to preserve order for BTreeSet, it would be better to use IndexSet here?
-			// But IndexSet won't allow fast ordered union...
-			// // Makes sense to preserve source ordering where possible.
-			// // May affect evaluation order, but it is not specified by jsonnet spec.
-			// #[cfg(feature = "exp-preserve-order")]
-			// true,
-			#[cfg(feature = "exp-preserve-order")]
-			false,
-		)
-		.into_iter()
-		.collect::<BTreeSet<IStr>>();
-	let patch_fields = patch
-		.fields(
-			// No need to look at the patch field order, I think?
-			// New fields (that will be appended at the end) will be alphabeticaly-ordered,
-			// but it is fine for jsonpatch, I don't think people write jsonpatch in jsonnet,
-			// when they can use mixins.
-			#[cfg(feature = "exp-preserve-order")]
-			false,
-		)
-		.into_iter()
-		.collect::<BTreeSet<IStr>>();
+	let target = target.as_obj().unwrap_or_else(ObjValue::new_empty);
 
 	let mut out = ObjValueBuilder::new();
-	for field in target_fields.union(&patch_fields) {
-		let Some(field_patch) = patch.get(field.clone())? else {
-			out.field(field.clone()).value(target.get(field.clone())?.expect("we're iterating over fields union, if field is missing in patch - it exists in target"));
+	for field in target.fields(
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	) {
+		if !patch.has_field(field.clone()) {
+			let value = target.get_lazy(field.clone()).expect("field exists");
+			out.field(field).thunk(value)?;
 			continue;
-		};
+		}
+		let field_patch = patch.get(field.clone())?.expect("field exists");
 		if matches!(field_patch, Val::Null) {
 			continue;
 		}
-		let Some(field_target) = target.get(field.clone())? else {
-			out.field(field.clone()).value(field_patch);
+		let field_target = target.get(field.clone())?.expect("field exists");
+		out.field(field).value(builtin_merge_patch(
+			field_target,
+			field_patch,
+			#[cfg(feature = "exp-preserve-order")]
+			preserve_order,
+		)?);
+	}
+	for field in patch.fields(
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	) {
+		if target.has_field(field.clone()) {
 			continue;
-		};
-		out.field(field.clone())
-			.value(builtin_merge_patch(field_target, field_patch)?);
+		}
+		let field_patch = patch.get(field.clone())?.expect("field exists");
+		if matches!(field_patch, Val::Null) {
+			continue;
+		}
+		// Missing target is treated as an empty object, so nulls are stripped from the nested patch
+		out.field(field).value(builtin_merge_patch(
+			Val::Null,
+			field_patch,
+			#[cfg(feature = "exp-preserve-order")]
+			preserve_order,
+		)?);
 	}
 	Ok(out.build().into())
 }
+
+#[derive(Trace, Clone, Copy)]
+enum ArrayMergeStrategy {
+	Replace,
+	Concat,
+	ByIndex,
+}
+
+#[derive(Trace, Clone, Copy)]
+struct DeepMergeOptions {
+	array_strategy: ArrayMergeStrategy,
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+}
+
+#[derive(Trace)]
+struct DeepMergeThunk {
+	a: Thunk<Val>,
+	b: Thunk<Val>,
+	options: DeepMergeOptions,
+}
+impl ThunkValue for DeepMergeThunk {
+	type Output = Val;
+
+	fn get(self: Box<Self>) -> Result<Val> {
+		deep_merge(self.a.evaluate()?, self.b.evaluate()?, self.options)
+	}
+}
+fn deep_merge_lazy(a: Thunk<Val>, b: Thunk<Val>, options: DeepMergeOptions) -> Thunk<Val> {
+	Thunk::new(DeepMergeThunk { a, b, options })
+}
+
+fn deep_merge(a: Val, b: Val, options: DeepMergeOptions) -> Result<Val> {
+	Ok(match (a, b) {
+		(Val::Obj(a), Val::Obj(b)) => {
+			let mut out = ObjValueBuilder::new();
+			for field in a.fields(
+				#[cfg(feature = "exp-preserve-order")]
+				options.preserve_order,
+			) {
+				let value = a.get_lazy(field.clone()).expect("field exists");
+				let value = if b.has_field(field.clone()) {
+					let patch = b.get_lazy(field.clone()).expect("field exists");
+					deep_merge_lazy(value, patch, options)
+				} else {
+					value
+				};
+				out.field(field).thunk(value)?;
+			}
+			for field in b.fields(
+				#[cfg(feature = "exp-preserve-order")]
+				options.preserve_order,
+			) {
+				if a.has_field(field.clone()) {
+					continue;
+				}
+				let value = b.get_lazy(field.clone()).expect("field exists");
+				out.field(field).thunk(value)?;
+			}
+			Val::Obj(out.build())
+		}
+		(Val::Arr(a), Val::Arr(b)) => Val::Arr(match options.array_strategy {
+			ArrayMergeStrategy::Replace => b,
+			ArrayMergeStrategy::Concat => ArrValue::extended(a, b),
+			ArrayMergeStrategy::ByIndex => ArrValue::lazy(
+				(0..a.len().max(b.len()))
+					.map(|i| match (a.get_lazy(i), b.get_lazy(i)) {
+						(Some(a), Some(b)) => deep_merge_lazy(a, b, options),
+						(Some(v), None) | (None, Some(v)) => v,
+						(None, None) => unreachable!("index is less than length of one of arrays"),
+					})
+					.collect(),
+			),
+		}),
+		// Scalars, and values of different types are replaced
+		(_, b) => b,
+	})
+}
+
+/// Recursively merges visible fields of `b` into `a`, the result is lazy, only the accessed fields
+/// are merged.
+///
+/// Arrays are merged according to `opts.arrayStrategy`: `replace` (default) keeps array from `b`,
+/// `concat` appends elements of `b` to `a`, and `byIndex` merges elements with the same index.
+/// Any other values, including values of different types, are replaced by value from `b`.
+///
+/// With `preserve_order`, fields of `a` are kept in place, and new fields are appended in `b` order.
+#[builtin]
+pub fn builtin_deep_merge(
+	a: Val,
+	b: Val,
+	opts: Option<ObjValue>,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<Val> {
+	let mut array_strategy = ArrayMergeStrategy::Replace;
+	if let Some(opts) = opts {
+		for field in opts.fields(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		) {
+			if &*field != "arrayStrategy" {
+				bail!("unknown deepMerge option <{field}>, expected arrayStrategy");
+			}
+		}
+		if let Some(strategy) = opts.get("arrayStrategy".into())? {
+			let strategy = IStr::from_untyped(strategy).description("arrayStrategy option")?;
+			array_strategy = match strategy.as_str() {
+				"replace" => ArrayMergeStrategy::Replace,
+				"concat" => ArrayMergeStrategy::Concat,
+				"byIndex" => ArrayMergeStrategy::ByIndex,
+				_ => bail!(
+					"unknown array strategy <{strategy}>, expected one of replace, concat, byIndex"
+				),
+			};
+		}
+	}
+	deep_merge(
+		a,
+		b,
+		DeepMergeOptions {
+			array_strategy,
+			#[cfg(feature = "exp-preserve-order")]
+			preserve_order,
+		},
+	)
+}
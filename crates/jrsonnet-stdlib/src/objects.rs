@@ -1,8 +1,12 @@
+use std::collections::BTreeSet;
+
 use jrsonnet_evaluator::{
-	function::builtin,
-	val::{ArrValue, Val},
-	IStr, ObjValue, ObjValueBuilder,
+	function::{builtin, FuncVal},
+	typed::Typed,
+	val::{ArrValue, ThunkValue, Val},
+	IStr, ObjValue, ObjValueBuilder, Result, ResultExt, Thunk,
 };
+use jrsonnet_gcmodule::Trace;
 
 #[builtin]
 pub fn builtin_object_fields_ex(
@@ -53,6 +57,55 @@ pub fn builtin_object_fields_all(
 	)
 }
 
+/// Describes every field of the object (including hidden) as `{ name, hidden, index }`, field values are not evaluated.
+///
+/// Fields are in the same order as in `std.objectFieldsAll`: sorted by name, or, with `preserve_order`,
+/// in declaration order, where overridden fields keep the position of their first definition.
+///
+/// `index` is the field position in declaration order, regardless of `preserve_order`. Declaration order
+/// is only tracked with `exp-preserve-order` feature, without it `index` is always `null`
+#[builtin]
+pub fn builtin_object_fields_info(
+	o: ObjValue,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> ArrValue {
+	#[cfg(feature = "exp-preserve-order")]
+	let fields = {
+		let mut fields: Vec<_> = o
+			.fields_ex(true, true)
+			.into_iter()
+			.enumerate()
+			.map(|(index, name)| {
+				(
+					name,
+					Val::Num(index.try_into().expect("index is in safe range")),
+				)
+			})
+			.collect();
+		if !preserve_order {
+			fields.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+		}
+		fields
+	};
+	#[cfg(not(feature = "exp-preserve-order"))]
+	let fields = o.fields_ex(true).into_iter().map(|name| (name, Val::Null));
+
+	fields
+		.into_iter()
+		.map(|(name, index)| {
+			let mut info = ObjValueBuilder::with_capacity(3);
+			info.field("name").value(Val::string(name.clone()));
+			info.field("hidden").value(Val::Bool(!o.has_field(name)));
+			info.field("index").value(index);
+			Val::Obj(info.build())
+		})
+		.collect::<Vec<_>>()
+		.into()
+}
+
 pub fn builtin_object_values_ex(
 	o: ObjValue,
 	include_hidden: bool,
@@ -108,9 +161,13 @@ pub fn builtin_object_keys_values_ex(
 		preserve_order,
 	)
 }
+/// Returns `[{key, value}]` array of object fields, values are evaluated lazily.
+///
+/// `inc_hidden` is an extension over go-jsonnet, `std.objectKeysValuesAll` is the same as `inc_hidden=true`.
 #[builtin]
 pub fn builtin_object_keys_values(
 	o: ObjValue,
+	#[default(false)] inc_hidden: bool,
 
 	#[default(false)]
 	#[cfg(feature = "exp-preserve-order")]
@@ -118,7 +175,7 @@ pub fn builtin_object_keys_values(
 ) -> ArrValue {
 	builtin_object_keys_values_ex(
 		o,
-		false,
+		inc_hidden,
 		#[cfg(feature = "exp-preserve-order")]
 		preserve_order,
 	)
@@ -154,6 +211,31 @@ pub fn builtin_object_has_all(o: ObjValue, f: IStr) -> bool {
 	o.has_field_include_hidden(f)
 }
 
+/// Copy of the object without the specified fields, other fields keep their visibility and stay lazy
+fn object_remove_keys(
+	obj: &ObjValue,
+	remove: impl Fn(&IStr) -> bool,
+	#[cfg(feature = "exp-preserve-order")] preserve_order: bool,
+) -> Result<ObjValue> {
+	let mut out = ObjValueBuilder::new();
+	for key in obj.fields_ex(
+		true,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	) {
+		if remove(&key) {
+			continue;
+		}
+		let value = obj.get_lazy(key.clone()).expect("field exists");
+		let mut field = out.field(key.clone());
+		if !obj.has_field(key) {
+			field = field.hide();
+		}
+		field.thunk(value)?;
+	}
+	Ok(out.build())
+}
+
 #[builtin]
 pub fn builtin_object_remove_key(
 	obj: ObjValue,
@@ -164,17 +246,218 @@ pub fn builtin_object_remove_key(
 	#[default(false)]
 	#[cfg(feature = "exp-preserve-order")]
 	preserve_order: bool,
-) -> ObjValue {
-	let mut new_obj = ObjValueBuilder::with_capacity(obj.len() - 1);
-	for (k, v) in obj.iter(
+) -> Result<ObjValue> {
+	object_remove_keys(
+		&obj,
+		|k| *k == key,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
+}
+
+#[builtin]
+pub fn builtin_object_remove_keys(
+	obj: ObjValue,
+	keys: Vec<IStr>,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	let keys = keys.into_iter().collect::<BTreeSet<_>>();
+	object_remove_keys(
+		&obj,
+		|k| keys.contains(k),
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
+}
+
+/// Keeps only fields for which `func(key, value)` returns true, field visibility is preserved.
+///
+/// Values are passed to `func` lazily, so they are only forced if `func` uses them
+/// (i.e filtering by key doesn't evaluate anything), kept fields stay lazy otherwise.
+fn object_filter(
+	func: &FuncVal,
+	obj: &ObjValue,
+	#[cfg(feature = "exp-preserve-order")] preserve_order: bool,
+) -> Result<ObjValue> {
+	let mut out = ObjValueBuilder::new();
+	for key in obj.fields_ex(
+		true,
 		#[cfg(feature = "exp-preserve-order")]
 		preserve_order,
 	) {
-		if k == key {
+		let value = obj.get_lazy(key.clone()).expect("field exists");
+		let keep = func
+			.evaluate_simple(&(key.clone(), value.clone()), false)
+			.and_then(bool::from_untyped)
+			.with_description(|| format!("field <{key}> predicate evaluation"))?;
+		if !keep {
 			continue;
 		}
-		new_obj.field(k).value(v.unwrap());
+		let mut field = out.field(key.clone());
+		if !obj.has_field(key) {
+			field = field.hide();
+		}
+		field.thunk(value)?;
 	}
+	Ok(out.build())
+}
+
+#[builtin]
+pub fn builtin_filter_fields(
+	func: FuncVal,
+	obj: ObjValue,
 
-	new_obj.build()
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	object_filter(
+		&func,
+		&obj,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
+}
+
+/// Same as `std.filterFields`, named consistently with `std.objectMap`
+#[builtin]
+pub fn builtin_object_filter(
+	pred: FuncVal,
+	o: ObjValue,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	object_filter(
+		&pred,
+		&o,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
+}
+
+/// Replaces every field value with `f(key, value)`, dropping fields for which it returns `null`,
+/// field visibility is preserved.
+///
+/// Unlike `std.objectMap`, results are evaluated eagerly, as they are needed to decide which fields
+/// to keep, values are still passed to `f` lazily.
+#[builtin]
+pub fn builtin_object_filter_map(
+	f: FuncVal,
+	o: ObjValue,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	let mut out = ObjValueBuilder::new();
+	for key in o.fields_ex(
+		true,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	) {
+		let value = o.get_lazy(key.clone()).expect("field exists");
+		let mapped = f
+			.evaluate_simple(&(key.clone(), value), false)
+			.with_description(|| format!("field <{key}> mapping"))?;
+		if matches!(mapped, Val::Null) {
+			continue;
+		}
+		let mut field = out.field(key.clone());
+		if !o.has_field(key) {
+			field = field.hide();
+		}
+		field.value(mapped);
+	}
+	Ok(out.build())
+}
+
+#[derive(Trace)]
+struct MappedFieldThunk {
+	func: FuncVal,
+	key: IStr,
+	value: Thunk<Val>,
+	with_key: bool,
+}
+impl ThunkValue for MappedFieldThunk {
+	type Output = Val;
+
+	fn get(self: Box<Self>) -> Result<Val> {
+		let key = self.key.clone();
+		if self.with_key {
+			self.func.evaluate_simple(&(self.key, self.value), false)
+		} else {
+			self.func.evaluate_simple(&(self.value,), false)
+		}
+		.with_description(|| format!("field <{key}> mapping"))
+	}
+}
+
+/// Copy of the object with every field value (including hidden) replaced by `func(value)`, or
+/// `func(key, value)` if `with_key` is set, field visibility is preserved, and mapped values stay lazy
+fn object_map(
+	func: FuncVal,
+	obj: &ObjValue,
+	with_key: bool,
+	#[cfg(feature = "exp-preserve-order")] preserve_order: bool,
+) -> Result<ObjValue> {
+	let mut out = ObjValueBuilder::new();
+	for key in obj.fields_ex(
+		true,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	) {
+		let value = obj.get_lazy(key.clone()).expect("field exists");
+		let mut field = out.field(key.clone());
+		if !obj.has_field(key.clone()) {
+			field = field.hide();
+		}
+		field.thunk(Thunk::new(MappedFieldThunk {
+			func: func.clone(),
+			key,
+			value,
+			with_key,
+		}))?;
+	}
+	Ok(out.build())
+}
+
+#[builtin]
+pub fn builtin_object_map(
+	f: FuncVal,
+	o: ObjValue,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	object_map(
+		f,
+		&o,
+		false,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
+}
+
+#[builtin]
+pub fn builtin_object_map_with_key(
+	f: FuncVal,
+	o: ObjValue,
+
+	#[default(false)]
+	#[cfg(feature = "exp-preserve-order")]
+	preserve_order: bool,
+) -> Result<ObjValue> {
+	object_map(
+		f,
+		&o,
+		true,
+		#[cfg(feature = "exp-preserve-order")]
+		preserve_order,
+	)
 }
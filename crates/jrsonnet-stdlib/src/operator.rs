@@ -6,8 +6,8 @@ use jrsonnet_evaluator::{
 	operator::evaluate_mod_op,
 	stdlib::std_format,
 	typed::{Either, Either2},
-	val::{equals, primitive_equals, NumValue},
-	IStr, Result, Val,
+	val::{equals, primitive_equals, ArrValue, NumValue},
+	IStr, ObjValue, Result, Val,
 };
 
 #[builtin]
@@ -32,6 +32,65 @@ pub fn builtin_equals(a: Val, b: Val) -> Result<bool> {
 	equals(&a, &b)
 }
 
+/// Same as `std.equals`, but with `includeHidden` objects are also compared by their hidden fields.
+/// Field visibility itself is not compared, so `{a:: 1}` is equal to `{a: 1}` in this mode.
+#[builtin]
+#[allow(non_snake_case)]
+pub fn builtin_deep_equal(a: Val, b: Val, #[default(false)] includeHidden: bool) -> Result<bool> {
+	if includeHidden {
+		equals_including_hidden(&a, &b)
+	} else {
+		equals(&a, &b)
+	}
+}
+
+fn equals_including_hidden(val_a: &Val, val_b: &Val) -> Result<bool> {
+	match (val_a, val_b) {
+		(Val::Arr(a), Val::Arr(b)) => {
+			if ArrValue::ptr_eq(a, b) {
+				return Ok(true);
+			}
+			if a.len() != b.len() {
+				return Ok(false);
+			}
+			for (a, b) in a.iter().zip(b.iter()) {
+				if !equals_including_hidden(&a?, &b?)? {
+					return Ok(false);
+				}
+			}
+			Ok(true)
+		}
+		(Val::Obj(a), Val::Obj(b)) => {
+			if ObjValue::ptr_eq(a, b) {
+				return Ok(true);
+			}
+			let fields = a.fields_ex(
+				true,
+				#[cfg(feature = "exp-preserve-order")]
+				false,
+			);
+			if fields
+				!= b.fields_ex(
+					true,
+					#[cfg(feature = "exp-preserve-order")]
+					false,
+				) {
+				return Ok(false);
+			}
+			for field in fields {
+				if !equals_including_hidden(
+					&a.get(field.clone())?.expect("field exists"),
+					&b.get(field)?.expect("field exists"),
+				)? {
+					return Ok(false);
+				}
+			}
+			Ok(true)
+		}
+		(a, b) => equals(a, b),
+	}
+}
+
 #[builtin]
 pub fn builtin_xor(x: bool, y: bool) -> bool {
 	x ^ y
@@ -42,6 +101,18 @@ pub fn builtin_xnor(x: bool, y: bool) -> bool {
 	x == y
 }
 
+/// Python-like `%` formatting, `vals` is either an array of positional values, an object for `%(name)s`
+/// references (with dotted paths for nested fields), or a single value.
+///
+/// Supported directives are `%[(name)][flags][width][.precision][length]type`, where:
+/// - flags are any of `#`, `0`, `-`, ` ` and `+`
+/// - width and precision are numbers, or `*` to take them from the values (not supported with objects)
+/// - length modifiers `h`, `l` and `L` are accepted and ignored
+/// - type is one of `d`/`i`/`u` (integer), `o` (octal), `x`/`X` (hex), `e`/`E` (exponent),
+///   `f`/`F` (fixed point), `g`/`G` (shortest of `e` and `f`), `c` (char from codepoint or 1-char string),
+///   `s` (value converted to string) and `%` (literal percent)
+///
+/// Any other conversion type is an error, which includes the whole offending directive.
 #[builtin]
 pub fn builtin_format(str: IStr, vals: Val) -> Result<String> {
 	std_format(&str, vals)
@@ -1,6 +1,10 @@
-use jrsonnet_evaluator::{function::builtin, runtime_error, IStr, Result, Val};
-use serde::Deserialize;
+use jrsonnet_evaluator::{
+	bail, function::builtin, runtime_error, IStr, ObjValue, Result, StrictValSeed, Val,
+};
 
+/// When the key is repeated, the last value wins, and with `exp-preserve-order` the field is ordered by its last occurrence.
+///
+/// With `exp-bigint`, integers outside of the f64 safe range are parsed as bigints
 #[builtin]
 pub fn builtin_parse_json(str: IStr) -> Result<Val> {
 	let value: Val =
@@ -8,8 +12,70 @@ pub fn builtin_parse_json(str: IStr) -> Result<Val> {
 	Ok(value)
 }
 
+/// Same as `std.parseJson`, but fails on duplicate object keys, referencing positions of both occurrences.
+#[builtin]
+pub fn builtin_parse_json_strict(str: IStr) -> Result<Val> {
+	use std::{cell::Cell, io};
+
+	use serde::de::DeserializeSeed;
+
+	/// Exposes the number of consumed bytes, `serde_json` reads input byte-by-byte,
+	/// so after the key is read it points right after its closing quote
+	struct CountingReader<'s> {
+		rest: &'s [u8],
+		consumed: &'s Cell<usize>,
+	}
+	impl io::Read for CountingReader<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			let read = self.rest.read(buf)?;
+			self.consumed.set(self.consumed.get() + read);
+			Ok(read)
+		}
+	}
+
+	let consumed = Cell::new(0);
+	let position = || consumed.get();
+	// `serde_json` reports errors for the repeated key at the colon following it,
+	// the first occurrence is reported the same way for consistency
+	let describe_position = |consumed: usize| {
+		let input = str.as_bytes();
+		let colon = consumed
+			+ input[consumed..]
+				.iter()
+				.take_while(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+				.count();
+		let before = &input[..(colon + 1).min(input.len())];
+		#[allow(clippy::naive_bytecount)]
+		let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+		let column = before.len()
+			- before
+				.iter()
+				.rposition(|&b| b == b'\n')
+				.map_or(0, |p| p + 1);
+		format!("line {line} column {column}")
+	};
+	let mut deserializer = serde_json::Deserializer::from_reader(CountingReader {
+		rest: str.as_bytes(),
+		consumed: &consumed,
+	});
+	let value = StrictValSeed {
+		position: &position,
+		describe_position: &describe_position,
+	}
+	.deserialize(&mut deserializer)
+	.and_then(|v| deserializer.end().map(|()| v))
+	.map_err(|e| runtime_error!("failed to parse json: {e}"))?;
+	Ok(value)
+}
+
+/// Multi-document input is parsed as an array of documents.
+///
+/// Scalar mapping keys are kept in their textual form, i.e `1: a` is parsed as `{"1": "a"}`,
+/// which is compatible with `std.parseYaml` in go-jsonnet. Arrays and objects as keys are rejected.
+#[cfg(feature = "yaml")]
 #[builtin]
 pub fn builtin_parse_yaml(str: IStr) -> Result<Val> {
+	use serde::Deserialize;
 	use serde_yaml_with_quirks::DeserializingQuirks;
 	let value = serde_yaml_with_quirks::Deserializer::from_str_with_quirks(
 		&str,
@@ -29,3 +95,126 @@ pub fn builtin_parse_yaml(str: IStr) -> Result<Val> {
 		Val::Arr(out.into())
 	})
 }
+
+/// Tables are parsed as objects, and datetimes are kept in their textual form.
+///
+/// Table order is preserved with `exp-preserve-order`.
+#[cfg(feature = "toml")]
+#[builtin]
+pub fn builtin_parse_toml(str: IStr) -> Result<ObjValue> {
+	let table: toml::Table = toml::from_str(&str).map_err(|e| {
+		// Multiline messages are produced for expectation lists
+		let message = e.message().replace('\n', ", ");
+		let Some(span) = e.span() else {
+			return runtime_error!("failed to parse toml: {message}");
+		};
+		let before = &str[..span.start];
+		let line = before.matches('\n').count() + 1;
+		let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+		runtime_error!("failed to parse toml: {message} at line {line} column {column}")
+	})?;
+	toml_table_to_obj(table)
+}
+
+#[cfg(feature = "toml")]
+fn toml_table_to_obj(table: toml::Table) -> Result<ObjValue> {
+	let mut out = ObjValue::builder_with_capacity(table.len());
+	for (key, value) in table {
+		out.field(key).value(toml_to_val(value)?);
+	}
+	Ok(out.build())
+}
+
+#[cfg(feature = "toml")]
+fn toml_to_val(value: toml::Value) -> Result<Val> {
+	use toml::Value;
+	Ok(match value {
+		Value::String(s) => Val::string(s),
+		#[allow(clippy::cast_precision_loss)]
+		Value::Integer(i) => Val::try_num(i as f64)?,
+		Value::Float(f) => Val::try_num(f)?,
+		Value::Boolean(b) => Val::Bool(b),
+		Value::Datetime(d) => Val::string(d.to_string()),
+		Value::Array(a) => Val::Arr(
+			a.into_iter()
+				.map(toml_to_val)
+				.collect::<Result<Vec<_>>>()?
+				.into(),
+		),
+		Value::Table(t) => Val::Obj(toml_table_to_obj(t)?),
+	})
+}
+
+/// Parses `key=value` lines (.env/.properties-like) into an object.
+///
+/// - Empty lines, and lines starting with `#` are skipped
+/// - Line ending with `\` is continued on the next line
+/// - Keys and values are trimmed, value may be wrapped in `'` (taken literally)
+///   or `"` (supports `\"`, `\\`, `\n` and `\t` escapes), in which case the whitespace inside of quotes is preserved
+/// - When the key is repeated, the last value wins
+///
+/// Variable interpolation and `export` prefixes are not supported.
+#[builtin]
+#[allow(non_snake_case)]
+pub fn builtin_parse_key_values(
+	str: IStr,
+	#[default("\n".into())] lineSep: IStr,
+	#[default("=".into())] kvSep: IStr,
+) -> Result<ObjValue> {
+	if lineSep.is_empty() {
+		bail!("line separator should not be empty");
+	}
+	if kvSep.is_empty() {
+		bail!("key-value separator should not be empty");
+	}
+	let mut out = ObjValue::builder();
+	let mut lines = str.split(&*lineSep).enumerate();
+	while let Some((i, line)) = lines.next() {
+		let mut line = line.trim().to_owned();
+		while line.ends_with('\\') {
+			line.pop();
+			let Some((_, next)) = lines.next() else {
+				break;
+			};
+			line.push_str(next.trim());
+		}
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let Some((key, value)) = line.split_once(&*kvSep) else {
+			bail!("line {}: missing key-value separator", i + 1);
+		};
+		let key = key.trim();
+		if key.is_empty() {
+			bail!("line {}: empty key", i + 1);
+		}
+		let value = unquote_value(value.trim())
+			.ok_or_else(|| runtime_error!("line {}: unterminated quoted value", i + 1))?;
+		out.field(key).value(Val::string(value));
+	}
+	Ok(out.build())
+}
+
+fn unquote_value(value: &str) -> Option<String> {
+	let Some(quote @ ('"' | '\'')) = value.chars().next() else {
+		return Some(value.to_owned());
+	};
+	let inner = value[1..].strip_suffix(quote)?;
+	if quote == '\'' {
+		return Some(inner.to_owned());
+	}
+	let mut out = String::with_capacity(inner.len());
+	let mut chars = inner.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next()? {
+			'n' => out.push('\n'),
+			't' => out.push('\t'),
+			c => out.push(c),
+		}
+	}
+	Some(out)
+}
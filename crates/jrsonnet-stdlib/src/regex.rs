@@ -2,8 +2,9 @@ use std::{cell::RefCell, hash::BuildHasherDefault, num::NonZeroUsize, rc::Rc};
 
 use ::regex::Regex;
 use jrsonnet_evaluator::{
+	bail,
 	error::{ErrorKind::*, Result},
-	val::StrValue,
+	val::{ArrValue, StrValue},
 	IStr, ObjValueBuilder, Val,
 };
 use jrsonnet_macros::builtin;
@@ -99,11 +100,71 @@ pub fn builtin_regex_full_match(
 	regex_match_inner(&regex, str)
 }
 
+/// Leading and trailing matches produce empty strings at the ends of the result,
+/// the same way `std.split` handles separators at the edges
+#[builtin(fields(
+    cache: RegexCache,
+))]
+pub fn builtin_split_regex(
+	this: &builtin_split_regex,
+	str: IStr,
+	pattern: IStr,
+	maxsplits: Option<i32>,
+) -> Result<ArrValue> {
+	let regex = this.cache.parse(pattern)?;
+	let parts: Vec<Val> = match maxsplits {
+		Some(n) if n >= 0 => regex
+			.splitn(&str, n as usize + 1)
+			.map(Val::string)
+			.collect(),
+		_ => regex.split(&str).map(Val::string).collect(),
+	};
+	Ok(ArrValue::eager(parts))
+}
+
 #[builtin]
 pub fn builtin_regex_quote_meta(pattern: String) -> String {
 	regex::escape(&pattern)
 }
 
+/// Regex crate silently replaces unknown groups with empty string, which hides typos
+fn check_replacement(regex: &Regex, to: &str) -> Result<()> {
+	let mut rest = to;
+	while let Some(pos) = rest.find('$') {
+		rest = &rest[pos + 1..];
+		if let Some(after) = rest.strip_prefix('$') {
+			rest = after;
+			continue;
+		}
+		let name = if let Some(braced) = rest.strip_prefix('{') {
+			let Some(end) = braced.find('}') else {
+				// Not a reference, regex crate outputs it literally
+				continue;
+			};
+			rest = &braced[end + 1..];
+			&braced[..end]
+		} else {
+			let end = rest
+				.find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+				.unwrap_or(rest.len());
+			let name = &rest[..end];
+			rest = &rest[end..];
+			name
+		};
+		if name.is_empty() {
+			continue;
+		}
+		let known = name.parse::<usize>().map_or_else(
+			|_| regex.capture_names().flatten().any(|n| n == name),
+			|idx| idx < regex.captures_len(),
+		);
+		if !known {
+			bail!("unknown capture group reference <{name}> in replacement");
+		}
+	}
+	Ok(())
+}
+
 #[builtin(fields(
     cache: RegexCache,
 ))]
@@ -114,6 +175,7 @@ pub fn builtin_regex_replace(
 	to: String,
 ) -> Result<String> {
 	let regex = this.cache.parse(pattern)?;
+	check_replacement(&regex, &to)?;
 	let replaced = regex.replace(&str, to);
 	Ok(replaced.to_string())
 }
@@ -128,6 +190,66 @@ pub fn builtin_regex_global_replace(
 	to: String,
 ) -> Result<String> {
 	let regex = this.cache.parse(pattern)?;
+	check_replacement(&regex, &to)?;
 	let replaced = regex.replace_all(&str, to);
 	Ok(replaced.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matched(pattern: &str, str: &str) -> Result<Val> {
+		let regex = RegexCacheInner::default().parse(pattern.into())?;
+		regex_match_inner(&regex, str.to_owned())
+	}
+
+	#[test]
+	fn match_captures() -> Result<()> {
+		let Val::Obj(out) = matched(r"v(?<major>\d+)\.(\d+)", "image:v1.25-alpine")? else {
+			panic!("should match");
+		};
+		assert_eq!(
+			out.get("string".into())?
+				.unwrap()
+				.as_str()
+				.unwrap()
+				.as_str(),
+			"v1.25"
+		);
+		let Val::Arr(captures) = out.get("captures".into())?.unwrap() else {
+			panic!("captures should be an array");
+		};
+		let captures = captures
+			.iter()
+			.map(|v| v.map(|v| v.as_str().unwrap().to_string()))
+			.collect::<Result<Vec<_>>>()?;
+		assert_eq!(captures, ["1", "25"]);
+		let Val::Obj(named) = out.get("namedCaptures".into())?.unwrap() else {
+			panic!("namedCaptures should be an object");
+		};
+		assert_eq!(
+			named
+				.get("major".into())?
+				.unwrap()
+				.as_str()
+				.unwrap()
+				.as_str(),
+			"1"
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn no_match() -> Result<()> {
+		assert!(matches!(matched(r"^\d+$", "abc")?, Val::Null));
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_pattern() {
+		let err = matched("(unclosed", "").unwrap_err().error().to_string();
+		assert!(err.starts_with("runtime error: regex parse failed"));
+		assert!(err.contains("(unclosed"));
+	}
+}
@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 
 use jrsonnet_evaluator::{
+	bail,
 	function::{builtin, FuncVal},
 	operator::evaluate_compare_op,
 	val::ArrValue,
@@ -32,39 +33,73 @@ pub fn builtin_set_member(x: Thunk<Val>, arr: ArrValue, keyF: Option<FuncVal>) -
 	Ok(false)
 }
 
+/// Iterator over set elements together with their keys, which checks that the set is sorted
+///
+/// Only the consumed part of the set is checked, so unsorted tail, which doesn't affect the
+/// result (i.e. for intersection) might not be detected.
+struct SortedSetIter<I, K> {
+	/// Argument name, for error messages
+	name: &'static str,
+	iter: I,
+	key_f: K,
+	index: usize,
+	prev_key: Option<Val>,
+}
+impl<I, K> SortedSetIter<I, K>
+where
+	I: Iterator<Item = Thunk<Val>>,
+	K: Fn(Thunk<Val>) -> Result<Val>,
+{
+	fn new(name: &'static str, iter: I, key_f: K) -> Self {
+		Self {
+			name,
+			iter,
+			key_f,
+			index: 0,
+			prev_key: None,
+		}
+	}
+	fn next(&mut self) -> Result<Option<(Thunk<Val>, Val)>> {
+		let Some(value) = self.iter.next() else {
+			return Ok(None);
+		};
+		let key = (self.key_f)(value.clone())?;
+		if let Some(prev_key) = &self.prev_key {
+			if evaluate_compare_op(&key, prev_key, BinaryOpType::Lt)? == Ordering::Less {
+				bail!(
+					"set <{}> is not sorted: element <{}> is less than the previous one",
+					self.name,
+					self.index,
+				);
+			}
+		}
+		self.prev_key = Some(key.clone());
+		self.index += 1;
+		Ok(Some((value, key)))
+	}
+}
+
 #[builtin]
-#[allow(non_snake_case, clippy::redundant_closure)]
+#[allow(non_snake_case)]
 pub fn builtin_set_inter(a: ArrValue, b: ArrValue, keyF: Option<FuncVal>) -> Result<ArrValue> {
-	let mut a = a.iter_lazy();
-	let mut b = b.iter_lazy();
-
 	let keyF = keyF
 		.unwrap_or(FuncVal::identity())
 		.into_native::<((Thunk<Val>,), Val)>();
-	let keyF = |v| keyF(v);
+	let mut a = SortedSetIter::new("a", a.iter_lazy(), &keyF);
+	let mut b = SortedSetIter::new("b", b.iter_lazy(), &keyF);
 
-	let mut av = a.next();
-	let mut bv = b.next();
-	let mut ak = av.clone().map(keyF).transpose()?;
-	let mut bk = bv.map(keyF).transpose()?;
+	let mut av = a.next()?;
+	let mut bv = b.next()?;
 
 	let mut out = Vec::new();
-	while let (Some(ac), Some(bc)) = (&ak, &bk) {
-		match evaluate_compare_op(ac, bc, BinaryOpType::Lt)? {
-			Ordering::Less => {
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
-			}
-			Ordering::Greater => {
-				bv = b.next();
-				bk = bv.map(keyF).transpose()?;
-			}
+	while let (Some((ae, ak)), Some((_, bk))) = (&av, &bv) {
+		match evaluate_compare_op(ak, bk, BinaryOpType::Lt)? {
+			Ordering::Less => av = a.next()?,
+			Ordering::Greater => bv = b.next()?,
 			Ordering::Equal => {
-				out.push(av.clone().expect("ak != None => av != None"));
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
-				bv = b.next();
-				bk = bv.map(keyF).transpose()?;
+				out.push(ae.clone());
+				av = a.next()?;
+				bv = b.next()?;
 			}
 		};
 	}
@@ -72,101 +107,80 @@ pub fn builtin_set_inter(a: ArrValue, b: ArrValue, keyF: Option<FuncVal>) -> Res
 }
 
 #[builtin]
-#[allow(non_snake_case, clippy::redundant_closure)]
+#[allow(non_snake_case)]
 pub fn builtin_set_diff(a: ArrValue, b: ArrValue, keyF: Option<FuncVal>) -> Result<ArrValue> {
-	let mut a = a.iter_lazy();
-	let mut b = b.iter_lazy();
-
 	let keyF = keyF
 		.unwrap_or(FuncVal::identity())
 		.into_native::<((Thunk<Val>,), Val)>();
-	let keyF = |v| keyF(v);
+	let mut a = SortedSetIter::new("a", a.iter_lazy(), &keyF);
+	let mut b = SortedSetIter::new("b", b.iter_lazy(), &keyF);
 
-	let mut av = a.next();
-	let mut bv = b.next();
-	let mut ak = av.clone().map(keyF).transpose()?;
-	let mut bk = bv.map(keyF).transpose()?;
+	let mut av = a.next()?;
+	let mut bv = b.next()?;
 
 	let mut out = Vec::new();
-	while let (Some(ac), Some(bc)) = (&ak, &bk) {
-		match evaluate_compare_op(ac, bc, BinaryOpType::Lt)? {
+	while let (Some((ae, ak)), Some((_, bk))) = (&av, &bv) {
+		match evaluate_compare_op(ak, bk, BinaryOpType::Lt)? {
 			Ordering::Less => {
 				// In a, but not in b
-				out.push(av.clone().expect("ak != None"));
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
-			}
-			Ordering::Greater => {
-				bv = b.next();
-				bk = bv.map(keyF).transpose()?;
+				out.push(ae.clone());
+				av = a.next()?;
 			}
+			Ordering::Greater => bv = b.next()?,
 			Ordering::Equal => {
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
-				bv = b.next();
-				bk = bv.map(keyF).transpose()?;
+				av = a.next()?;
+				bv = b.next()?;
 			}
 		};
 	}
-	while let Some(_ac) = &ak {
+	while let Some((ae, _)) = av {
 		// In a, but not in b
-		out.push(av.clone().expect("ak != None"));
-		av = a.next();
-		ak = av.clone().map(keyF).transpose()?;
+		out.push(ae);
+		av = a.next()?;
 	}
 	Ok(ArrValue::lazy(out))
 }
 
 #[builtin]
-#[allow(non_snake_case, clippy::redundant_closure)]
+#[allow(non_snake_case)]
 pub fn builtin_set_union(a: ArrValue, b: ArrValue, keyF: Option<FuncVal>) -> Result<ArrValue> {
-	let mut a = a.iter_lazy();
-	let mut b = b.iter_lazy();
-
 	let keyF = keyF
 		.unwrap_or(FuncVal::identity())
 		.into_native::<((Thunk<Val>,), Val)>();
-	let keyF = |v| keyF(v);
+	let mut a = SortedSetIter::new("a", a.iter_lazy(), &keyF);
+	let mut b = SortedSetIter::new("b", b.iter_lazy(), &keyF);
 
-	let mut av = a.next();
-	let mut bv = b.next();
-	let mut ak = av.clone().map(keyF).transpose()?;
-	let mut bk = bv.clone().map(keyF).transpose()?;
+	let mut av = a.next()?;
+	let mut bv = b.next()?;
 
 	let mut out = Vec::new();
-	while let (Some(ac), Some(bc)) = (&ak, &bk) {
-		match evaluate_compare_op(ac, bc, BinaryOpType::Lt)? {
+	while let (Some((ae, ak)), Some((be, bk))) = (&av, &bv) {
+		match evaluate_compare_op(ak, bk, BinaryOpType::Lt)? {
 			Ordering::Less => {
-				out.push(av.clone().expect("ak != None"));
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
+				out.push(ae.clone());
+				av = a.next()?;
 			}
 			Ordering::Greater => {
-				out.push(bv.clone().expect("bk != None"));
-				bv = b.next();
-				bk = bv.clone().map(keyF).transpose()?;
+				out.push(be.clone());
+				bv = b.next()?;
 			}
 			Ordering::Equal => {
 				// NOTE: order matters, values in `a` win
-				out.push(av.clone().expect("ak != None"));
-				av = a.next();
-				ak = av.clone().map(keyF).transpose()?;
-				bv = b.next();
-				bk = bv.clone().map(keyF).transpose()?;
+				out.push(ae.clone());
+				av = a.next()?;
+				bv = b.next()?;
 			}
 		};
 	}
 	// a.len() > b.len()
-	while let Some(_ac) = &ak {
-		out.push(av.clone().expect("ak != None"));
-		av = a.next();
-		ak = av.clone().map(keyF).transpose()?;
+	while let Some((ae, _)) = av {
+		out.push(ae);
+		av = a.next()?;
 	}
 	// b.len() > a.len()
-	while let Some(_bc) = &bk {
-		out.push(bv.clone().expect("ak != None"));
-		bv = b.next();
-		bk = bv.clone().map(keyF).transpose()?;
+	while let Some((be, _)) = bv {
+		out.push(be);
+		bv = b.next()?;
 	}
 	Ok(ArrValue::lazy(out))
 }
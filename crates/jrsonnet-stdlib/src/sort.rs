@@ -1,13 +1,15 @@
 #![allow(non_snake_case)]
 
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::HashSet};
 
 use jrsonnet_evaluator::{
 	bail,
 	function::{builtin, FuncVal},
+	manifest::JsonFormat,
 	operator::evaluate_compare_op,
+	typed::Typed,
 	val::{equals, ArrValue},
-	Result, Thunk, Val,
+	ObjValue, Result, ResultExt, Thunk, Val,
 };
 use jrsonnet_parser::BinaryOpType;
 
@@ -69,7 +71,11 @@ fn sort_identity(mut values: Vec<Val>) -> Result<Vec<Val>> {
 	Ok(values)
 }
 
-fn sort_keyf(values: ArrValue, keyf: FuncVal) -> Result<Vec<Thunk<Val>>> {
+/// Sorts by the key, computed once per element, sort is stable.
+///
+/// With `reverse`, elements are sorted in descending order, while elements with equal keys
+/// are still kept in the original order.
+fn sort_keyf(values: ArrValue, keyf: FuncVal, reverse: bool) -> Result<Vec<Thunk<Val>>> {
 	// Slow path, user provided key getter
 	let mut vk = Vec::with_capacity(values.len());
 	for value in values.iter_lazy() {
@@ -78,14 +84,15 @@ fn sort_keyf(values: ArrValue, keyf: FuncVal) -> Result<Vec<Thunk<Val>>> {
 			keyf.evaluate_simple(&(value.clone(),), false)?,
 		));
 	}
+	let direction = |ord: Ordering| if reverse { ord.reverse() } else { ord };
 	let sort_type = get_sort_type(&vk, |v| &v.1)?;
 	match sort_type {
-		SortKeyType::Number => vk.sort_by_key(|v| match v.1 {
-			Val::Num(n) => n,
+		SortKeyType::Number => vk.sort_by(|(_a, ak), (_b, bk)| match (ak, bk) {
+			(Val::Num(a), Val::Num(b)) => direction(a.cmp(b)),
 			_ => unreachable!(),
 		}),
-		SortKeyType::String => vk.sort_by_key(|v| match &v.1 {
-			Val::Str(s) => s.clone(),
+		SortKeyType::String => vk.sort_by(|(_a, ak), (_b, bk)| match (ak, bk) {
+			(Val::Str(a), Val::Str(b)) => direction(a.cmp(b)),
 			_ => unreachable!(),
 		}),
 		SortKeyType::Unknown => {
@@ -94,7 +101,7 @@ fn sort_keyf(values: ArrValue, keyf: FuncVal) -> Result<Vec<Thunk<Val>>> {
 			// jsonnet perspective
 			vk.sort_by(
 				|(_a, ak), (_b, bk)| match evaluate_compare_op(ak, bk, BinaryOpType::Lt) {
-					Ok(ord) => ord,
+					Ok(ord) => direction(ord),
 					Err(e) if err.is_none() => {
 						let _ = err.insert(e);
 						Ordering::Equal
@@ -120,7 +127,7 @@ pub fn sort(values: ArrValue, key_getter: FuncVal) -> Result<ArrValue> {
 			values.iter().collect::<Result<Vec<Val>>>()?,
 		)?))
 	} else {
-		Ok(ArrValue::lazy(sort_keyf(values, key_getter)?))
+		Ok(ArrValue::lazy(sort_keyf(values, key_getter, false)?))
 	}
 }
 
@@ -133,6 +140,81 @@ pub fn builtin_sort(
 	super::sort::sort(arr, keyF)
 }
 
+/// Same as `std.sort` with `keyF`, but always calls `keyF` exactly once per element,
+/// and supports stable descending sort.
+#[builtin]
+pub fn builtin_sort_by(
+	arr: ArrValue,
+	keyF: FuncVal,
+	#[default(false)] reverse: bool,
+) -> Result<ArrValue> {
+	if arr.is_empty() {
+		return Ok(arr);
+	}
+	Ok(ArrValue::lazy(sort_keyf(arr, keyF, reverse)?))
+}
+
+/// Sorts by multiple keys, as in SQL `ORDER BY`: `keys` is an array of `{key: function, reverse?: bool}`,
+/// the first key is primary, next keys are only compared when the previous ones are equal.
+///
+/// Sort is stable, and every key function is called exactly once per element.
+#[builtin]
+pub fn builtin_sort_multi(arr: ArrValue, keys: Vec<ObjValue>) -> Result<ArrValue> {
+	let mut key_fns = Vec::with_capacity(keys.len());
+	for (i, key) in keys.into_iter().enumerate() {
+		for field in key.fields_ex(
+			true,
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		) {
+			if !matches!(field.as_str(), "key" | "reverse") {
+				bail!("unknown field <{field}> in sort key <{i}>, expected <key> or <reverse>");
+			}
+		}
+		let Some(key_fn) = key.get("key".into())? else {
+			bail!("sort key <{i}> should have <key> field");
+		};
+		let key_fn = FuncVal::from_untyped(key_fn)
+			.with_description(|| format!("sort key <{i}> <key> field"))?;
+		let reverse = match key.get("reverse".into())? {
+			Some(reverse) => bool::from_untyped(reverse)
+				.with_description(|| format!("sort key <{i}> <reverse> field"))?,
+			None => false,
+		};
+		key_fns.push((key_fn, reverse));
+	}
+
+	let mut vk = Vec::with_capacity(arr.len());
+	for value in arr.iter_lazy() {
+		let mut element_keys = Vec::with_capacity(key_fns.len());
+		for (key_fn, _) in &key_fns {
+			element_keys.push(key_fn.evaluate_simple(&(value.clone(),), false)?);
+		}
+		vk.push((value, element_keys));
+	}
+
+	let mut err = None;
+	vk.sort_by(|(_a, ak), (_b, bk)| {
+		for ((a, b), (_, reverse)) in ak.iter().zip(bk.iter()).zip(&key_fns) {
+			let ord = match evaluate_compare_op(a, b, BinaryOpType::Lt) {
+				Ok(ord) => ord,
+				Err(e) => {
+					err.get_or_insert(e);
+					return Ordering::Equal;
+				}
+			};
+			if ord != Ordering::Equal {
+				return if *reverse { ord.reverse() } else { ord };
+			}
+		}
+		Ordering::Equal
+	});
+	if let Some(err) = err {
+		return Err(err);
+	}
+	Ok(ArrValue::lazy(vk.into_iter().map(|v| v.0).collect()))
+}
+
 fn uniq_identity(arr: Vec<Val>) -> Result<Vec<Val>> {
 	let mut out = Vec::new();
 	let mut last = arr[0].clone();
@@ -181,6 +263,31 @@ pub fn builtin_uniq(
 	}
 }
 
+/// Unlike `std.uniq`, removes all duplicates, not only adjacent ones, keeping the first occurence.
+///
+/// Keys are compared by their minified json representation, which is stored in a hash set,
+/// so this is O(n) in the number of elements (plus the cost of manifesting keys).
+/// Keys which can't be manifested (i.e functions) result in error.
+#[builtin]
+pub fn builtin_uniq_by(arr: ArrValue, keyF: FuncVal) -> Result<ArrValue> {
+	let format = JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	);
+	let mut seen = HashSet::new();
+	let mut out = Vec::new();
+	for (i, item) in arr.iter_lazy().enumerate() {
+		let key = keyF
+			.evaluate_simple(&(item.clone(),), false)
+			.and_then(|key| key.manifest(&format))
+			.with_description(|| format!("elem <{i}> key evaluation"))?;
+		if seen.insert(key) {
+			out.push(item);
+		}
+	}
+	Ok(ArrValue::lazy(out))
+}
+
 #[builtin]
 #[allow(non_snake_case)]
 pub fn builtin_set(
@@ -197,7 +304,7 @@ pub fn builtin_set(
 		let arr = uniq_identity(arr)?;
 		Ok(ArrValue::eager(arr))
 	} else {
-		let arr = sort_keyf(arr, keyF.clone())?;
+		let arr = sort_keyf(arr, keyF.clone(), false)?;
 		let arr = uniq_keyf(ArrValue::lazy(arr), keyF)?;
 		Ok(ArrValue::lazy(arr))
 	}
@@ -211,13 +318,17 @@ fn eval_keyf(val: Val, key_f: &Option<FuncVal>) -> Result<Val> {
 	}
 }
 
+/// Key is evaluated once per element, on ties the first element wins
 fn array_top1(arr: ArrValue, key_f: Option<FuncVal>, ordering: Ordering) -> Result<Val> {
-	let mut iter = arr.iter();
-	let mut min = iter.next().expect("not empty")?;
-	let mut min_key = eval_keyf(min.clone(), &key_f)?;
-	for item in iter {
+	let mut iter = arr.iter().enumerate();
+	let (_, min) = iter.next().expect("not empty");
+	let mut min = min?;
+	let mut min_key =
+		eval_keyf(min.clone(), &key_f).with_description(|| "elem <0> key evaluation")?;
+	for (i, item) in iter {
 		let cur = item?;
-		let cur_key = eval_keyf(cur.clone(), &key_f)?;
+		let cur_key = eval_keyf(cur.clone(), &key_f)
+			.with_description(|| format!("elem <{i}> key evaluation"))?;
 		if evaluate_compare_op(&cur_key, &min_key, BinaryOpType::Lt)? == ordering {
 			min = cur;
 			min_key = cur_key;
@@ -248,3 +359,18 @@ pub fn builtin_max_array(
 	}
 	array_top1(arr, keyF, Ordering::Greater)
 }
+
+#[builtin]
+pub fn builtin_min_by(arr: ArrValue, keyF: FuncVal) -> Result<Val> {
+	if arr.is_empty() {
+		bail!("minBy of empty array");
+	}
+	array_top1(arr, Some(keyF), Ordering::Less)
+}
+#[builtin]
+pub fn builtin_max_by(arr: ArrValue, keyF: FuncVal) -> Result<Val> {
+	if arr.is_empty() {
+		bail!("maxBy of empty array");
+	}
+	array_top1(arr, Some(keyF), Ordering::Greater)
+}
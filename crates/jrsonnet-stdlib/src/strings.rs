@@ -14,9 +14,36 @@ pub const fn builtin_codepoint(str: char) -> u32 {
 	str as u32
 }
 
+/// Indices are in codepoints, out-of-range values are clamped instead of erroring:
+///
+/// - Negative `from` counts from the end of the string, `-1` being the last codepoint,
+///   if it points before the start of the string, it is clamped to `0`
+/// - `from` past the end of the string results in an empty string
+/// - Negative `len` is treated as `0`, `len` past the end of the string takes the rest of the string
 #[builtin]
-pub fn builtin_substr(str: IStr, from: usize, len: usize) -> String {
-	str.chars().skip(from).take(len).collect()
+pub fn builtin_substr(str: IStr, from: i64, len: i64) -> IStr {
+	// Offsets which don't fit in usize are past the end of any string anyway
+	let from = if from < 0 {
+		let count = str.chars().count();
+		count.saturating_sub(usize::try_from(from.unsigned_abs()).unwrap_or(usize::MAX))
+	} else {
+		usize::try_from(from).unwrap_or(usize::MAX)
+	};
+	let len = usize::try_from(len.max(0)).unwrap_or(usize::MAX);
+	// Byte offsets of codepoint boundaries
+	let mut bounds = str.char_indices().map(|(i, _)| i).chain([str.len()]);
+	let Some(start) = bounds.nth(from) else {
+		return IStr::empty();
+	};
+	let end = if len == 0 {
+		start
+	} else {
+		bounds.nth(len - 1).unwrap_or(str.len())
+	};
+	if start == 0 && end == str.len() {
+		return str;
+	}
+	str[start..end].into()
 }
 
 #[builtin]
@@ -29,10 +56,11 @@ pub fn builtin_str_replace(str: String, from: IStr, to: IStr) -> String {
 	str.replace(&from as &str, &to as &str)
 }
 
+/// Wraps string in single quotes, embedded single quotes are replaced with `'\''`
 #[builtin]
 pub fn builtin_escape_string_bash(str_: String) -> String {
 	const QUOTE: char = '\'';
-	let mut out = str_.replace(QUOTE, "'\"'\"'");
+	let mut out = str_.replace(QUOTE, "'\\''");
 	out.insert(0, QUOTE);
 	out.push(QUOTE);
 	out
@@ -43,29 +71,41 @@ pub fn builtin_escape_string_dollars(str_: String) -> String {
 	str_.replace('$', "$$")
 }
 
+/// Compares strings ignoring the case of all Unicode characters, not only ASCII ones.
+///
+/// Every character is mapped to uppercase and then to lowercase, which matches the full Unicode
+/// case folding for practically all characters: `ß` equals `ss`/`SS`, and final `ς` equals `σ`/`Σ`
 #[builtin]
-pub fn builtin_is_empty(str: String) -> bool {
-	str.is_empty()
-}
-
-#[builtin]
-pub fn builtin_equals_ignore_case(str1: String, str2: String) -> bool {
-	str1.to_ascii_lowercase() == str2.to_ascii_lowercase()
+pub fn builtin_equals_ignore_case(str1: IStr, str2: IStr) -> bool {
+	fn fold_case(s: &str) -> impl Iterator<Item = char> + '_ {
+		s.chars()
+			.flat_map(char::to_uppercase)
+			.flat_map(char::to_lowercase)
+	}
+	fold_case(&str1).eq(fold_case(&str2))
 }
 
 #[builtin]
-pub fn builtin_splitlimit(str: IStr, c: IStr, maxsplits: Either![usize, M1]) -> ArrValue {
+pub fn builtin_splitlimit(str: IStr, c: IStr, maxsplits: Either![usize, M1]) -> Result<ArrValue> {
 	use Either2::*;
-	match maxsplits {
+	if c.is_empty() {
+		bail!("std.splitLimit second parameter should have length 1 or greater")
+	}
+	Ok(match maxsplits {
 		A(n) => str.splitn(n + 1, &c as &str).map(Val::string).collect(),
 		B(_) => str.split(&c as &str).map(Val::string).collect(),
-	}
+	})
 }
 
+/// Same as `std.splitLimit`, but splits are counted from the end of the string,
+/// i.e `std.splitLimitR('a.b.c', '.', 1) == ['a.b', 'c']`
 #[builtin]
-pub fn builtin_splitlimitr(str: IStr, c: IStr, maxsplits: Either![usize, M1]) -> ArrValue {
+pub fn builtin_splitlimitr(str: IStr, c: IStr, maxsplits: Either![usize, M1]) -> Result<ArrValue> {
 	use Either2::*;
-	match maxsplits {
+	if c.is_empty() {
+		bail!("std.splitLimitR second parameter should have length 1 or greater")
+	}
+	Ok(match maxsplits {
 		A(n) =>
 		// rsplitn does not implement DoubleEndedIterator so collect into
 		// a temporary vec
@@ -78,25 +118,82 @@ pub fn builtin_splitlimitr(str: IStr, c: IStr, maxsplits: Either![usize, M1]) ->
 				.collect()
 		}
 		B(_) => str.split(&c as &str).map(Val::string).collect(),
-	}
+	})
 }
 
 #[builtin]
-pub fn builtin_split(str: IStr, c: IStr) -> ArrValue {
+pub fn builtin_split(str: IStr, c: IStr) -> Result<ArrValue> {
 	use Either2::*;
 	builtin_splitlimit(str, c, B(M1))
 }
 
+/// Only `a-z` are converted, so the length (in both bytes and chars) is preserved.
+/// Input is returned as-is if there is nothing to convert.
+#[builtin]
+pub fn builtin_ascii_upper(str: IStr) -> IStr {
+	if !str.bytes().any(|b| b.is_ascii_lowercase()) {
+		return str;
+	}
+	str.to_ascii_uppercase().into()
+}
+
+/// Only `A-Z` are converted, so the length (in both bytes and chars) is preserved.
+/// Input is returned as-is if there is nothing to convert.
+#[builtin]
+pub fn builtin_ascii_lower(str: IStr) -> IStr {
+	if !str.bytes().any(|b| b.is_ascii_uppercase()) {
+		return str;
+	}
+	str.to_ascii_lowercase().into()
+}
+
+/// Full Unicode case mapping, length is NOT preserved, i.e `ß` becomes `SS`
+#[builtin]
+pub fn builtin_to_upper(str: IStr) -> IStr {
+	str.to_uppercase().into()
+}
+
+/// Full Unicode case mapping, length is NOT preserved, i.e `İ` becomes `i̇`
 #[builtin]
-pub fn builtin_ascii_upper(str: IStr) -> String {
-	str.to_ascii_uppercase()
+pub fn builtin_to_lower(str: IStr) -> IStr {
+	str.to_lowercase().into()
 }
 
+/// Prepends `prefix` to every line of `str`.
+///
+/// Empty lines are only prefixed when `emptyLines` is set, the empty remainder after the trailing newline
+/// is never prefixed, so `"a\n"` becomes `"<prefix>a\n"`.
+/// With `firstLine` unset, the first line is left as-is, which is useful when the string is placed after an already-indented key.
 #[builtin]
-pub fn builtin_ascii_lower(str: IStr) -> String {
-	str.to_ascii_lowercase()
+#[allow(non_snake_case)]
+pub fn builtin_indent(
+	str: IStr,
+	prefix: IStr,
+	#[default(true)] firstLine: bool,
+	#[default(false)] emptyLines: bool,
+) -> IStr {
+	if prefix.is_empty() || str.is_empty() {
+		return str;
+	}
+	let body = str.strip_suffix('\n');
+	let trailing_newline = body.is_some();
+	let mut out = String::with_capacity(str.len());
+	for (i, line) in body.unwrap_or(&str).split('\n').enumerate() {
+		if i != 0 {
+			out.push('\n');
+		}
+		if (i != 0 || firstLine) && (emptyLines || !line.is_empty()) {
+			out.push_str(&prefix);
+		}
+		out.push_str(line);
+	}
+	if trailing_newline {
+		out.push('\n');
+	}
+	out.into()
 }
 
+/// Returns char indices of non-overlapping occurrences of `pat`, empty `pat` is never found
 #[builtin]
 pub fn builtin_find_substr(pat: IStr, str: IStr) -> ArrValue {
 	if pat.is_empty() || str.is_empty() || pat.len() > str.len() {
@@ -110,12 +207,14 @@ pub fn builtin_find_substr(pat: IStr, str: IStr) -> ArrValue {
 	let max_pos = str.len() - pat.len();
 
 	let mut out: Vec<Val> = Vec::new();
+	let mut next_allowed = 0;
 	for (ch_idx, (i, _)) in str
 		.char_indices()
 		.take_while(|(i, _)| i <= &max_pos)
 		.enumerate()
 	{
-		if &strb[i..i + pat.len()] == pat {
+		if i >= next_allowed && &strb[i..i + pat.len()] == pat {
+			next_allowed = i + pat.len();
 			out.push(Val::Num(
 				ch_idx.try_into().expect("unrealisticly long string"),
 			));
@@ -231,7 +330,10 @@ pub fn builtin_lstrip_chars(str: IStr, chars: IndexableVal) -> Result<IStr> {
 	}
 
 	let pattern = new_trim_pattern(chars)?;
-	Ok(str.as_str().trim_start_matches(pattern).into())
+	Ok(keep_if_untrimmed(
+		&str,
+		str.as_str().trim_start_matches(pattern),
+	))
 }
 
 #[builtin]
@@ -241,7 +343,10 @@ pub fn builtin_rstrip_chars(str: IStr, chars: IndexableVal) -> Result<IStr> {
 	}
 
 	let pattern = new_trim_pattern(chars)?;
-	Ok(str.as_str().trim_end_matches(pattern).into())
+	Ok(keep_if_untrimmed(
+		&str,
+		str.as_str().trim_end_matches(pattern),
+	))
 }
 
 #[builtin]
@@ -251,7 +356,15 @@ pub fn builtin_strip_chars(str: IStr, chars: IndexableVal) -> Result<IStr> {
 	}
 
 	let pattern = new_trim_pattern(chars)?;
-	Ok(str.as_str().trim_matches(pattern).into())
+	Ok(keep_if_untrimmed(&str, str.as_str().trim_matches(pattern)))
+}
+
+/// Avoids reinterning when nothing was stripped
+fn keep_if_untrimmed(str: &IStr, trimmed: &str) -> IStr {
+	if trimmed.len() == str.len() {
+		return str.clone();
+	}
+	trimmed.into()
 }
 
 fn new_trim_pattern(chars: IndexableVal) -> Result<impl Fn(char) -> bool> {
@@ -0,0 +1,86 @@
+use std::fmt::Write;
+
+use jrsonnet_evaluator::{bail, IStr, Result};
+use jrsonnet_macros::builtin;
+
+const SECS_PER_DAY: i64 = 86400;
+
+fn is_leap(year: i64) -> bool {
+	year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Converts days since unix epoch to (year, month, day)
+///
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
+
+fn day_of_year(year: i64, month: i64, day: i64) -> i64 {
+	const CUMULATIVE: [i64; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+	#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+	let before = CUMULATIVE[(month - 1) as usize];
+	before + day + i64::from(month > 2 && is_leap(year))
+}
+
+/// Formats unix timestamp (in seconds, fractional part is discarded) in UTC.
+///
+/// Supported directives are `%Y %m %d %H %M %S %j %z %%`, `%z` is always `+0000`
+#[builtin]
+pub fn builtin_strftime(format: IStr, timestamp: f64) -> Result<String> {
+	if !timestamp.is_finite() {
+		bail!("timestamp should be finite, got {timestamp}");
+	}
+	#[allow(clippy::cast_possible_truncation)]
+	let timestamp = timestamp.floor() as i64;
+	let days = timestamp.div_euclid(SECS_PER_DAY);
+	let secs = timestamp.rem_euclid(SECS_PER_DAY);
+	let (year, month, day) = civil_from_days(days);
+
+	let mut out = String::with_capacity(format.len());
+	let mut chars = format.chars();
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('Y') => write!(out, "{year:04}"),
+			Some('m') => write!(out, "{month:02}"),
+			Some('d') => write!(out, "{day:02}"),
+			Some('H') => write!(out, "{:02}", secs / 3600),
+			Some('M') => write!(out, "{:02}", secs / 60 % 60),
+			Some('S') => write!(out, "{:02}", secs % 60),
+			Some('j') => write!(out, "{:03}", day_of_year(year, month, day)),
+			Some('z') => write!(out, "+0000"),
+			Some('%') => write!(out, "%"),
+			Some(c) => bail!("unknown strftime directive <%{c}>"),
+			None => bail!("truncated strftime directive at the end of format"),
+		}
+		.expect("write to string can't fail");
+	}
+	Ok(out)
+}
+
+/// Current unix timestamp in seconds.
+///
+/// This function is impure, and is only available with `exp-impure-now` feature
+#[cfg(feature = "exp-impure-now")]
+#[builtin]
+pub fn builtin_now() -> Result<f64> {
+	use std::time::{SystemTime, UNIX_EPOCH};
+
+	let Ok(since_epoch) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+		bail!("system time is before unix epoch");
+	};
+	Ok(since_epoch.as_secs_f64())
+}
@@ -1,5 +1,6 @@
 use jrsonnet_evaluator::{function::builtin, IStr, Val};
 
+/// Name of the value type, exactly as it is spelled in type error messages
 #[builtin]
 pub fn builtin_type(x: Val) -> IStr {
 	x.value_type().name().into()
@@ -96,6 +96,7 @@ pub enum ValType {
 }
 
 impl ValType {
+	/// Type name, used in type error messages, and returned by `std.type`
 	pub const fn name(&self) -> &'static str {
 		use ValType::*;
 		match self {
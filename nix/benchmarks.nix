@@ -366,5 +366,42 @@ in
         skipGo = skipSlow;
         skipScala = skipSlow;
       }}
+
+      echo >> $out
+      echo "## Manifestification" >> $out
+      ${mkBench {
+        name = "Object with 10k clean keys";
+        path = ./benchmarks/manifest_clean_keys.jsonnet;
+        skipCpp = skipSlow;
+        skipScala = skipSlow;
+      }}
+
+      echo >> $out
+      echo "## Sorting" >> $out
+      ${mkBench {
+        name = "std.sortBy with expensive key";
+        path = ./benchmarks/sort_by_cached_key.jsonnet;
+        skipRustAlternative = "std.sortBy is jrsonnet-specific";
+        skipGo = "std.sortBy is jrsonnet-specific";
+        skipScala = "std.sortBy is jrsonnet-specific";
+        skipCpp = "std.sortBy is jrsonnet-specific";
+      }}
+      ${mkBench {
+        name = "Merge sort calling expensive key in every comparison";
+        path = ./benchmarks/sort_key_in_comparison.jsonnet;
+        skipCpp = skipSlow;
+        skipScala = skipSlow;
+      }}
+
+      echo >> $out
+      echo "## Folding" >> $out
+      ${mkBench {
+        name = "std.foldlIndexed over 1M elements";
+        path = ./benchmarks/fold_indexed.jsonnet;
+        skipRustAlternative = "std.foldlIndexed is jrsonnet-specific";
+        skipGo = "std.foldlIndexed is jrsonnet-specific";
+        skipScala = "std.foldlIndexed is jrsonnet-specific";
+        skipCpp = "std.foldlIndexed is jrsonnet-specific";
+      }}
     '';
   }
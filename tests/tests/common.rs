@@ -84,6 +84,19 @@ impl ContextInitializerT for ContextInitializer {
 		bobj.method("assertThrow", assert_throw::INST);
 		bobj.method("paramNames", param_names::INST);
 
+		// Crate features, to test behavior depending on them
+		let mut features = ObjValueBuilder::new();
+		for (feature, enabled) in [
+			("exp-preserve-order", cfg!(feature = "exp-preserve-order")),
+			("exp-bigint", cfg!(feature = "exp-bigint")),
+			("exp-regex", cfg!(feature = "exp-regex")),
+			("toml", cfg!(feature = "toml")),
+			("graphemes", cfg!(feature = "graphemes")),
+		] {
+			features.field(feature).value(Val::Bool(enabled));
+		}
+		bobj.field("features").value(Val::Obj(features.build()));
+
 		builder.bind("test", Thunk::evaluated(Val::Obj(bobj.build())));
 	}
 
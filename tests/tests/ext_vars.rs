@@ -0,0 +1,66 @@
+use jrsonnet_evaluator::{bail, manifest::JsonFormat, trace::PathResolver, Result, State};
+use jrsonnet_stdlib::ContextInitializer;
+
+mod common;
+
+#[test]
+fn ext_vars_object() -> Result<()> {
+	let mut state = State::builder();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	std.add_ext_str("str".into(), "hello".into());
+	std.add_ext_code("code", "std.length('abc')")?;
+	std.add_ext_code("broken", "error 'not forced'")?;
+	state.context_initializer(std);
+	let state = state.build();
+
+	let v = state.evaluate_snippet(
+		"test",
+		"local vars = std.extVars(); [std.objectFields(vars), vars.str, vars.code]",
+	)?;
+	ensure_eq!(
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+		r#"[["broken","code","str"],"hello",3]"#
+	);
+	let v = state.evaluate_snippet("test", "std.extVars().code == std.extVar('code')")?;
+	ensure_eq!(v.as_bool(), Some(true));
+	Ok(())
+}
+
+#[test]
+fn ext_var_json() -> Result<()> {
+	let mut state = State::builder();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	std.add_ext_str("config".into(), r#"{"a": 1, "b": [true]}"#.into());
+	std.add_ext_str("broken".into(), "{a: 1}".into());
+	std.add_ext_code("code", "{a: 1}")?;
+	state.context_initializer(std);
+	let state = state.build();
+
+	let v = state.evaluate_snippet("test", "std.extVarJson('config')")?;
+	ensure_eq!(
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+		r#"{"a":1,"b":[true]}"#
+	);
+
+	let Err(e) = state.evaluate_snippet("test", "std.extVarJson('broken')") else {
+		bail!("malformed json should fail");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: failed to parse external variable <broken> as json: key must be a string at line 1 column 2"
+	);
+	let Err(e) = state.evaluate_snippet("test", "std.extVarJson('code')") else {
+		bail!("non-string variable should fail");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: external variable <code> should be a string, got object"
+	);
+	Ok(())
+}
@@ -0,0 +1,73 @@
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use jrsonnet_evaluator::{trace::PathResolver, Result, State, Val};
+use jrsonnet_stdlib::ContextInitializer;
+
+mod common;
+
+/// Keeps track of the peak heap usage
+struct PeakAllocator {
+	current: AtomicUsize,
+	peak: AtomicUsize,
+}
+impl PeakAllocator {
+	/// Starts a new measurement, returns currently allocated bytes
+	fn reset(&self) -> usize {
+		let current = self.current.load(Ordering::SeqCst);
+		self.peak.store(current, Ordering::SeqCst);
+		current
+	}
+	fn peak(&self) -> usize {
+		self.peak.load(Ordering::SeqCst)
+	}
+}
+unsafe impl GlobalAlloc for PeakAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let current = self.current.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+		self.peak.fetch_max(current, Ordering::SeqCst);
+		// Safety: forwarded as is
+		unsafe { System.alloc(layout) }
+	}
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		self.current.fetch_sub(layout.size(), Ordering::SeqCst);
+		// Safety: forwarded as is
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator {
+	current: AtomicUsize::new(0),
+	peak: AtomicUsize::new(0),
+};
+
+/// Folds shouldn't collect the array, so the memory used doesn't depend on its length.
+///
+/// Single test in this binary, to not have allocations from other tests running in parallel.
+#[test]
+fn fold_memory_is_flat() -> Result<()> {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::new_cwd_fallback()));
+	let s = s.build();
+
+	let peak_usage = |code: String| -> Result<(Val, usize)> {
+		let base = ALLOCATOR.reset();
+		let v = s.evaluate_snippet("snip".to_owned(), code)?;
+		Ok((v, ALLOCATOR.peak() - base))
+	};
+
+	for (func, f) in [
+		("foldlIndexed", "function(acc, i, x) acc + i * (x % 2)"),
+		("foldrIndexed", "function(i, x, acc) acc + i * (x % 2)"),
+	] {
+		let (small, small_peak) = peak_usage(format!("std.{func}({f}, std.range(0, 999), 0)"))?;
+		ensure_val_eq!(small, Val::Num(250_000.into()));
+		let (large, large_peak) = peak_usage(format!("std.{func}({f}, std.range(0, 99999), 0)"))?;
+		ensure_val_eq!(large, Val::try_num(2_500_000_000_u64)?);
+		// 100k of collected `Val`s alone would take 1.6MB
+		ensure!(large_peak < small_peak + 64 * 1024);
+	}
+	Ok(())
+}
@@ -0,0 +1,845 @@
+use jrsonnet_evaluator::{
+	bail,
+	manifest::{
+		escape_string_json, escape_string_json_write, FlatIndexStyle, FlatPathFormat, JsonFormat,
+		K8sStreamFormat, LazyDebugFormat, ManifestFormat, RenameCollision, SizeLimited,
+		StringFormat, WithHeaderFooter, YamlStreamFormat,
+	},
+	trace::PathResolver,
+	FileImportResolver, IStr, Result, State,
+};
+use jrsonnet_stdlib::{verify_hash_footer, ContextInitializer, HashFooterFormat, YamlFormat};
+
+mod common;
+
+fn state() -> State {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::new_cwd_fallback()))
+		.import_resolver(FileImportResolver::default());
+	s.build()
+}
+
+#[test]
+fn chunks_concat_to_manifest() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: [1, 2], b: {c: 'd'}, h:: 1}")?;
+	let format = JsonFormat::default();
+	let chunks = format
+		.manifest_chunks(v.clone())
+		.collect::<Result<Vec<_>>>()?;
+	ensure_eq!(chunks.len(), 2);
+	ensure_eq!(chunks.concat(), v.manifest(&format)?);
+	Ok(())
+}
+
+#[test]
+fn chunks_error_on_failing_element() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "[1, 2, error 'third', 4]")?;
+	let format = JsonFormat::default();
+	let mut chunks = format.manifest_chunks(v);
+
+	ensure_eq!(chunks.next().transpose()?, Some("[\n    1".to_owned()));
+	ensure_eq!(chunks.next().transpose()?, Some(",\n    2".to_owned()));
+	let Some(Err(e)) = chunks.next() else {
+		bail!("third chunk should fail");
+	};
+	ensure_eq!(e.error().to_string(), "runtime error: third");
+	ensure!(chunks.next().is_none());
+	Ok(())
+}
+
+#[test]
+fn single_quoted_strings() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", r#"{"it's": ['say "hi"', "'"]}"#)?;
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_quote('\'')?
+		)?,
+		r#"{'it\'s':['say "hi"','\'']}"#,
+	);
+	ensure_eq!(
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+		r#"{"it's":["say \"hi\"","'"]}"#,
+	);
+	for quote in ['\\', 'ё', 'n', '`'] {
+		let Err(e) = JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)
+		.with_quote(quote) else {
+			bail!("{quote:?} should be rejected as a quote");
+		};
+		ensure_eq!(
+			e.error().to_string(),
+			format!("runtime error: quote should be either '\"' or '\\'', got {quote:?}")
+		);
+	}
+	Ok(())
+}
+
+#[cfg(feature = "exp-bigint")]
+#[test]
+fn single_quoted_bigint() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "[std.bigint('100000000000000000000')]")?;
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_quote('\'')?
+		)?,
+		"['100000000000000000000']",
+	);
+	Ok(())
+}
+
+#[test]
+fn minified_has_no_whitespace() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet(
+		"snip",
+		r#"std.manifestJsonMinified({
+			"key with spaces": ["a b", "quoted \" space", 1.5, null, {}, []],
+			nested: {x: [true, false, {y: " "}]},
+		})"#,
+	)?;
+	let out = v.as_str().expect("string");
+
+	let mut in_string = false;
+	let mut escaped = false;
+	for c in out.chars() {
+		if in_string {
+			match c {
+				_ if escaped => escaped = false,
+				'\\' => escaped = true,
+				'"' => in_string = false,
+				_ => {}
+			}
+		} else if c == '"' {
+			in_string = true;
+		} else {
+			ensure!(!c.is_whitespace());
+		}
+	}
+	ensure_eq!(
+		out.as_str(),
+		r#"{"key with spaces":["a b","quoted \" space",1.5,null,{},[]],"nested":{"x":[true,false,{"y":" "}]}}"#,
+	);
+	Ok(())
+}
+
+#[test]
+fn quote_unsafe_integers() -> Result<()> {
+	let s = state();
+	// 2^53 + 1 is rounded to 2^53 on parse
+	let v = s.evaluate_snippet(
+		"snip",
+		"[9007199254740993, -9007199254740993, 9007199254740991, 0.5]",
+	)?;
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_quote_unsafe_integers(true)
+		)?,
+		r#"["9007199254740992","-9007199254740992",9007199254740991,0.5]"#,
+	);
+	ensure_eq!(
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+		"[9007199254740992,-9007199254740992,9007199254740991,0.5]",
+	);
+	Ok(())
+}
+
+#[test]
+fn elements_one_per_line() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet(
+		"snip",
+		"{items: [{a: 1, b: [1, 2]}, {a: 2, b: []}, {a: 3, b: {c: 'd'}}], n: 1}",
+	)?;
+	let format = JsonFormat::default().with_elements_one_per_line(true);
+	ensure_eq!(
+		v.manifest(&format)?,
+		r#"{
+    "items": [
+        {"a":1,"b":[1,2]},
+        {"a":2,"b":[]},
+        {"a":3,"b":{"c":"d"}}
+    ],
+    "n": 1
+}"#,
+	);
+
+	let v = s.evaluate_snippet("snip", "[{a: 1}, {a: 2}, {a: 3}]")?;
+	ensure_eq!(
+		v.manifest(&format)?,
+		"[\n    {\"a\":1},\n    {\"a\":2},\n    {\"a\":3}\n]",
+	);
+	let chunks = format
+		.manifest_chunks(v.clone())
+		.collect::<Result<Vec<_>>>()?;
+	ensure_eq!(chunks.concat(), v.manifest(&format)?);
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_elements_one_per_line(true)
+		)?,
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+	);
+	Ok(())
+}
+
+#[test]
+fn rename_collision() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a_b: 1, aB: 2, c: {d_e: 3}}")?;
+	let camel = |key: &IStr| -> Result<IStr> {
+		let mut out = String::new();
+		let mut upper = false;
+		for c in key.chars() {
+			if c == '_' {
+				upper = true;
+			} else if upper {
+				upper = false;
+				out.extend(c.to_uppercase());
+			} else {
+				out.push(c);
+			}
+		}
+		Ok(out.into())
+	};
+
+	let Err(e) = v.manifest(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)
+		.with_key_rename(camel),
+	) else {
+		bail!("collision should fail by default");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: fields <aB> and <a_b> are both renamed to <aB>",
+	);
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_key_rename(camel)
+			.with_rename_collision(RenameCollision::LastWins)
+		)?,
+		r#"{"aB":1,"c":{"dE":3}}"#,
+	);
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_key_rename(camel)
+			.with_rename_collision(RenameCollision::First)
+		)?,
+		r#"{"aB":2,"c":{"dE":3}}"#,
+	);
+
+	let format = JsonFormat::default().with_key_rename(camel);
+	let Some(Err(_)) = format.manifest_chunks(v).next() else {
+		bail!("collision should fail in chunked manifest");
+	};
+	Ok(())
+}
+
+#[test]
+fn hash_footer() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: [1, 2], b: 'c'}")?;
+	let out = HashFooterFormat::new(JsonFormat::default()).manifest(v.clone())?;
+	let Some((content, footer)) = out.rsplit_once('\n') else {
+		bail!("footer should be on its own line");
+	};
+	ensure_eq!(content, v.manifest(JsonFormat::default())?);
+	let expected = s.evaluate_snippet("snip", format!("std.sha256({content:?})"))?;
+	ensure_eq!(
+		footer,
+		format!("// sha256: {}", expected.as_str().expect("string"))
+	);
+
+	ensure_eq!(verify_hash_footer(&out), Some(true));
+	ensure_eq!(verify_hash_footer(&format!("{out}\n")), Some(true));
+	ensure_eq!(
+		verify_hash_footer(&out.replace("\"c\"", "\"d\"")),
+		Some(false)
+	);
+	ensure_eq!(verify_hash_footer(content), None);
+	Ok(())
+}
+
+#[test]
+fn max_depth() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: [1, {b: []}], c: 'd'}")?;
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_max_depth(4)
+		)?,
+		r#"{"a":[1,{"b":[]}],"c":"d"}"#,
+	);
+	let Err(e) = v.manifest(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)
+		.with_max_depth(3),
+	) else {
+		bail!("depth limit should be exceeded");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: manifest depth limit exceeded"
+	);
+
+	let format = JsonFormat::default().with_max_depth(1);
+	let mut chunks = format.manifest_chunks(v);
+	let Some(Err(_)) = chunks.next() else {
+		bail!("depth limit should be exceeded in chunked manifest");
+	};
+
+	let scalar = s.evaluate_snippet("snip", "'str'")?;
+	ensure_eq!(
+		scalar.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_max_depth(0)
+		)?,
+		r#""str""#
+	);
+
+	let deep = s.evaluate_snippet(
+		"snip",
+		"std.foldl(function(acc, _) [acc], std.range(1, 500), [])",
+	)?;
+	let Err(e) = deep.manifest(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)
+		.with_max_depth(100),
+	) else {
+		bail!("depth limit should be exceeded");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: manifest depth limit exceeded"
+	);
+	Ok(())
+}
+
+#[test]
+fn deep_nesting() -> Result<()> {
+	let s = state();
+	// Deeper than the default stack frame limit
+	let v = s.evaluate_snippet(
+		"snip",
+		"std.foldl(function(acc, i) if i % 2 == 0 then [acc] else {a: acc}, std.range(1, 2000), [])",
+	)?;
+	let out = v.manifest(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))?;
+	ensure_eq!(
+		out,
+		format!("{}[]{}", r#"[{"a":"#.repeat(1000), "}]".repeat(1000))
+	);
+	let chunks = JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	)
+	.manifest_chunks(v)
+	.collect::<Result<Vec<_>>>()?;
+	ensure_eq!(chunks.concat(), out);
+	Ok(())
+}
+
+#[test]
+fn non_finite_numbers() -> Result<()> {
+	let s = state();
+	for code in [
+		"1e308 * 10",
+		"std.pow(10, 400)",
+		"std.log(0)",
+		"[1, {a: std.exp(1000)}]",
+	] {
+		let Err(e) = s
+			.evaluate_snippet("snip", code)
+			.and_then(|v| v.manifest(JsonFormat::default()))
+		else {
+			bail!("non-finite number should not be manifested: {code}");
+		};
+		ensure_eq!(e.error().to_string(), "convert num value: non-finite");
+	}
+	Ok(())
+}
+
+#[test]
+fn escape_to_writer() -> Result<()> {
+	struct Failing;
+	impl std::io::Write for Failing {
+		fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+			Err(std::io::Error::other("closed"))
+		}
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	for s in [
+		"",
+		"plain",
+		"quote \" and \\",
+		"tab\tnl\n\u{1}",
+		"юникод 🦀",
+		"trailing\n",
+	] {
+		let mut out = Vec::new();
+		escape_string_json_write(s, &mut out).expect("vec write");
+		ensure_eq!(
+			String::from_utf8(out).expect("utf-8"),
+			escape_string_json(s)
+		);
+	}
+
+	let Err(e) = escape_string_json_write("str", &mut Failing) else {
+		bail!("writer error should be propagated");
+	};
+	ensure_eq!(e.to_string(), "closed");
+	Ok(())
+}
+
+#[test]
+fn escape_forward_slash() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", r#"{"</script>": ["<script>a / b</script>"]}"#)?;
+	ensure_eq!(
+		v.manifest(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			)
+			.with_escape_forward_slash(true)
+		)?,
+		r#"{"<\/script>":["<script>a \/ b<\/script>"]}"#,
+	);
+	ensure_eq!(
+		v.manifest(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false
+		))?,
+		r#"{"</script>":["<script>a / b</script>"]}"#,
+	);
+	let chunks = JsonFormat::default()
+		.with_escape_forward_slash(true)
+		.manifest_chunks(v)
+		.collect::<Result<Vec<_>>>()?;
+	ensure_eq!(
+		chunks.concat(),
+		"{\n    \"<\\/script>\": [\n        \"<script>a \\/ b<\\/script>\"\n    ]\n}"
+	);
+	Ok(())
+}
+
+#[test]
+fn header_footer() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: 1}")?;
+	let format = WithHeaderFooter::new(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		),
+		"// GENERATED, DO NOT EDIT\n",
+		"\n// end\n",
+	);
+	ensure_eq!(
+		format.manifest(v.clone())?,
+		"// GENERATED, DO NOT EDIT\n{\"a\":1}\n// end\n"
+	);
+	ensure!(!format.file_trailing_newline());
+
+	let format = WithHeaderFooter::header(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		),
+		"# header\n",
+	);
+	ensure_eq!(format.manifest(v.clone())?, "# header\n{\"a\":1}");
+	ensure!(format.file_trailing_newline());
+
+	let format = WithHeaderFooter::footer(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		),
+		"\n# footer",
+	);
+	ensure_eq!(format.manifest(v)?, "{\"a\":1}\n# footer");
+	ensure!(format.file_trailing_newline());
+	ensure!(!WithHeaderFooter::header(StringFormat, "# header\n").file_trailing_newline());
+
+	let stream = s.evaluate_snippet("snip", "[1, 'a']")?;
+	let format = WithHeaderFooter::header(
+		YamlStreamFormat::cli(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)),
+		"# GENERATED\n",
+	);
+	ensure_eq!(
+		format.manifest(stream)?,
+		"# GENERATED\n---\n1\n---\n\"a\"\n..."
+	);
+	Ok(())
+}
+
+#[test]
+fn size_limited() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: [1, 2, 3]}")?;
+	ensure_eq!(
+		SizeLimited::new(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false
+			),
+			13
+		)
+		.manifest(v.clone())?,
+		r#"{"a":[1,2,3]}"#
+	);
+
+	let mut buf = "prefix".to_owned();
+	let Err(e) = SizeLimited::new(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		),
+		12,
+	)
+	.manifest_buf(v.clone(), &mut buf) else {
+		bail!("size limit should be exceeded");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: manifest size limit of 12 bytes exceeded"
+	);
+	ensure_eq!(buf, "prefix");
+
+	let format = SizeLimited::new(
+		WithHeaderFooter::header(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false,
+			),
+			"# h\n",
+		),
+		17,
+	);
+	ensure_eq!(format.manifest(v)?, "# h\n{\"a\":[1,2,3]}");
+	ensure!(!SizeLimited::new(StringFormat, 10).file_trailing_newline());
+	Ok(())
+}
+
+#[test]
+fn size_limited_fails_early() -> Result<()> {
+	let s = state();
+	// Failing last element would be reached, if the whole value was manifested before the check
+	let v = s.evaluate_snippet(
+		"snip",
+		"std.makeArray(1e6, function(i) if i == 1e6 - 1 then error 'last element' else {n: i})",
+	)?;
+	let formats: [Box<dyn ManifestFormat>; 4] = [
+		Box::new(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)),
+		Box::new(JsonFormat::default()),
+		Box::new(YamlFormat::cli(
+			2,
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)),
+		Box::new(HashFooterFormat::new(YamlStreamFormat::cli(
+			JsonFormat::minify(
+				#[cfg(feature = "exp-preserve-order")]
+				false,
+			),
+		))),
+	];
+	for format in formats {
+		let mut buf = String::new();
+		let Err(e) = SizeLimited::new(format, 1000).manifest_buf(v.clone(), &mut buf) else {
+			bail!("size limit should be exceeded");
+		};
+		ensure_eq!(
+			e.error().to_string(),
+			"runtime error: manifest size limit of 1000 bytes exceeded"
+		);
+		ensure_eq!(buf, "");
+	}
+	Ok(())
+}
+
+#[test]
+fn yaml_stream_wrap_single() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "{a: 1}")?;
+	let Err(e) = YamlStreamFormat::cli(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))
+	.manifest(v.clone()) else {
+		bail!("non-array should be rejected by default");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: output should be array for yaml stream format, got object"
+	);
+
+	let format = YamlStreamFormat::cli(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))
+	.with_wrap_single(true);
+	ensure_eq!(format.manifest(v)?, "---\n{\"a\":1}\n...");
+	let stream = s.evaluate_snippet("snip", "[1, 2]")?;
+	ensure_eq!(format.manifest(stream)?, "---\n1\n---\n2\n...");
+	Ok(())
+}
+
+#[test]
+fn yaml_stream_leading_separator() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet("snip", "[1, 2]")?;
+	let format = YamlStreamFormat::cli(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	));
+	ensure_eq!(format.manifest(v.clone())?, "---\n1\n---\n2\n...");
+	let format = YamlStreamFormat::cli(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))
+	.with_leading_separator(false);
+	ensure_eq!(format.manifest(v.clone())?, "1\n---\n2\n...");
+	let format = YamlStreamFormat::std_yaml_stream(
+		JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		),
+		false,
+	)
+	.with_leading_separator(false);
+	ensure_eq!(format.manifest(v)?, "1\n---\n2\n\n");
+	Ok(())
+}
+
+#[test]
+fn lazy_debug() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet(
+		"snip",
+		"{a: 1, b: error 'fail', c: [1 + 1, 2 + 2, 3 + 3], d: std.repeat('x', 20), e: {}, f: function(x) x}",
+	)?;
+	let format = LazyDebugFormat::new().with_truncate_strings(Some(10));
+	ensure_eq!(
+		format.manifest(v.clone())?,
+		"{\n   \"a\": <thunk>,\n   \"b\": <thunk>,\n   \"c\": <thunk>,\n   \"d\": <thunk>,\n   \"e\": <thunk>,\n   \"f\": <thunk>\n}"
+	);
+
+	let obj = v.as_obj().expect("object");
+	obj.get("a".into())?;
+	ensure!(obj.get("b".into()).is_err());
+	let c = obj.get("c".into())?.expect("field exists");
+	c.as_arr().expect("array").get(1)?;
+	obj.get("d".into())?;
+	obj.get("e".into())?;
+	obj.get("f".into())?;
+	ensure_eq!(
+		format.manifest(v)?,
+		"{\n   \"a\": 1,\n   \"b\": <error: runtime error: fail>,\n   \"c\": [\n      <thunk>,\n      4,\n      <thunk>\n   ],\n   \"d\": \"xxxxx..xxxxx\",\n   \"e\": { },\n   \"f\": <function>\n}"
+	);
+	Ok(())
+}
+
+#[test]
+fn flat_path() -> Result<()> {
+	let s = state();
+	let v = s.evaluate_snippet(
+		"snip",
+		"{metadata: {name: 'x', labels: {}}, spec: {replicas: 3, ports: [{port: 80}, 443], tags: []}}",
+	)?;
+	ensure_eq!(
+		FlatPathFormat::new().manifest(v)?,
+		"{\n    \"metadata.labels\": {},\n    \"metadata.name\": \"x\",\n    \"spec.ports[0].port\": 80,\n    \"spec.ports[1]\": 443,\n    \"spec.replicas\": 3,\n    \"spec.tags\": []\n}"
+	);
+	let format = FlatPathFormat::new()
+		.with_separator("/")
+		.with_index_style(FlatIndexStyle::Segment);
+	ensure_eq!(
+		format.manifest(s.evaluate_snippet("snip", "{a: [[1], {b: null}]}")?)?,
+		"{\n    \"a/0/0\": 1,\n    \"a/1/b\": null\n}"
+	);
+	// Top-level values
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "[true, [2]]")?)?,
+		"{\n    \"[0]\": true,\n    \"[1][0]\": 2\n}"
+	);
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "{}")?)?,
+		"{ }"
+	);
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "'x'")?)?,
+		"\"x\""
+	);
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "[]")?)?,
+		"{ }"
+	);
+	// Empty field names
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "{'': 1}")?)?,
+		"{\n    \"\": 1\n}"
+	);
+	ensure_eq!(
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "{'': {a: 1}, a: {'': 2}}")?)?,
+		"{\n    \".a\": 1,\n    \"a.\": 2\n}"
+	);
+
+	for (code, path) in [
+		("{'a.b': 1, a: {b: 2}}", "a.b"),
+		("{'a[0]': 1, a: [2]}", "a[0]"),
+	] {
+		let Err(e) = FlatPathFormat::new().manifest(s.evaluate_snippet("snip", code)?) else {
+			bail!("colliding paths should fail: {code}");
+		};
+		ensure_eq!(
+			e.error().to_string(),
+			format!("runtime error: path <{path}> is produced by multiple leaves")
+		);
+	}
+
+	let Err(e) =
+		FlatPathFormat::new().manifest(s.evaluate_snippet("snip", "{a: [function(x) x]}")?)
+	else {
+		bail!("functions can't be manifested");
+	};
+	ensure_eq!(
+		e.to_string(),
+		"runtime error: tried to manifest function\n\telem <0> manifestification\n\tfield <a> manifestification\n"
+	);
+	Ok(())
+}
+
+#[test]
+fn escape_clean_and_dirty_edges() -> Result<()> {
+	ensure_eq!(escape_string_json("plain key"), "\"plain key\"");
+	ensure_eq!(escape_string_json(""), "\"\"");
+	ensure_eq!(escape_string_json("\"start"), "\"\\\"start\"");
+	ensure_eq!(escape_string_json("end\n"), "\"end\\n\"");
+	ensure_eq!(escape_string_json("mid\tdle"), "\"mid\\tdle\"");
+	Ok(())
+}
+
+#[test]
+fn k8s_stream() -> Result<()> {
+	let s = state();
+	let priority = [
+		("Namespace".into(), 0),
+		("CustomResourceDefinition".into(), 1),
+		("ServiceAccount".into(), 2),
+		("Deployment".into(), 3),
+	];
+	let format = K8sStreamFormat::new(
+		YamlStreamFormat::cli(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		)),
+		priority.clone(),
+	);
+	let v = s.evaluate_snippet(
+		"snip",
+		"[{kind: 'Deployment', n: 1}, {n: 2}, {kind: 'Widget'}, {kind: 'Namespace'}, {kind: 'Deployment', n: 3}, {kind: 'CustomResourceDefinition'}]",
+	)?;
+	ensure_eq!(
+		format.manifest(v)?,
+		"---\n{\"kind\":\"Namespace\"}\n---\n{\"kind\":\"CustomResourceDefinition\"}\n---\n{\"kind\":\"Deployment\",\"n\":1}\n---\n{\"kind\":\"Deployment\",\"n\":3}\n---\n{\"kind\":\"Widget\"}\n---\n{\"n\":2}\n..."
+	);
+	ensure_eq!(format.manifest(s.evaluate_snippet("snip", "[]")?)?, "...");
+
+	for (code, error) in [
+		(
+			"[{kind: 'Namespace'}, 1]",
+			"runtime error: elem <1> should be an object, got number",
+		),
+		(
+			"[{kind: 1}]",
+			"runtime error: elem <0> kind should be a string, got number",
+		),
+		(
+			"{kind: 'Namespace'}",
+			"runtime error: output should be array for yaml stream format, got object",
+		),
+	] {
+		let Err(e) = format.manifest(s.evaluate_snippet("snip", code)?) else {
+			bail!("{code} should be rejected");
+		};
+		ensure_eq!(e.error().to_string(), error);
+	}
+
+	let format = K8sStreamFormat::new(
+		YamlStreamFormat::cli(JsonFormat::minify(
+			#[cfg(feature = "exp-preserve-order")]
+			false,
+		))
+		.with_wrap_single(true),
+		priority,
+	);
+	ensure_eq!(
+		format.manifest(s.evaluate_snippet("snip", "{kind: 'Namespace'}")?)?,
+		"---\n{\"kind\":\"Namespace\"}\n..."
+	);
+	Ok(())
+}
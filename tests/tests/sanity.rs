@@ -1,5 +1,8 @@
+use std::time::{Duration, Instant};
+
 use jrsonnet_evaluator::{
 	bail,
+	manifest::JsonFormat,
 	trace::{CompactFormat, PathResolver, TraceFormat},
 	FileImportResolver, Result, State, Val,
 };
@@ -48,3 +51,198 @@ fn assert_negative() -> Result<()> {
 
 	Ok(())
 }
+
+fn step_limited_state(limit: u64) -> State {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::new_cwd_fallback()))
+		.evaluation_step_limit(limit);
+	s.build()
+}
+
+#[test]
+fn evaluation_step_limit() -> Result<()> {
+	let trace_format = CompactFormat::default();
+	let mut steps = vec![];
+	for _ in 0..2 {
+		let s = step_limited_state(50);
+		let Err(e) = s.evaluate_snippet("snip".to_owned(), "local f(x) = f(x); f(0)") else {
+			bail!("evaluation should be aborted");
+		};
+		let e = trace_format.format(&e).unwrap();
+		ensure!(e.starts_with("evaluation step limit exceeded\n"));
+		steps.push(s.evaluation_steps());
+	}
+	// Limit is reached deterministically
+	ensure_eq!(steps, vec![50, 50]);
+
+	// Exceeded limit can't be caught
+	let s = step_limited_state(50);
+	let Err(e) = s.evaluate_snippet(
+		"snip".to_owned(),
+		"local f(x) = f(x); std.try(f(0), 'fallback')",
+	) else {
+		bail!("evaluation should be aborted");
+	};
+	let e = trace_format.format(&e).unwrap();
+	ensure!(e.starts_with("evaluation step limit exceeded\n"));
+
+	let s = step_limited_state(1000);
+	let v = s.evaluate_snippet(
+		"snip".to_owned(),
+		"std.foldl(function(a, b) a + b, [1, 2, 3], 0)",
+	)?;
+	ensure_val_eq!(v, Val::Num(6.into()));
+	ensure!(s.evaluation_steps() <= 1000);
+
+	// Limit is applied to every top-level evaluation, not to the lifetime of the state
+	let code = "std.foldl(function(a, b) a + b, std.range(0, 200), 0)";
+	let s = step_limited_state(1000);
+	s.evaluate_snippet("snip".to_owned(), code)?;
+	let steps = s.evaluation_steps();
+	ensure!(steps > 500);
+	s.evaluate_snippet("snip".to_owned(), code)?;
+	ensure_eq!(s.evaluation_steps(), steps);
+
+	Ok(())
+}
+
+#[test]
+fn evaluation_timeout() -> Result<()> {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::new_cwd_fallback()))
+		.evaluation_timeout(Duration::from_millis(50));
+	let s = s.build();
+
+	let started = Instant::now();
+	let Err(e) = s.evaluate_snippet(
+		"snip".to_owned(),
+		"std.foldl(function(a, b) a + b, std.range(0, 100000000), 0)",
+	) else {
+		bail!("evaluation should time out");
+	};
+	let e = CompactFormat::default().format(&e).unwrap();
+	ensure!(e.starts_with("evaluation timeout\n"));
+	ensure!(started.elapsed() < Duration::from_secs(10));
+
+	// Timer is restarted by every top-level evaluation, so the state is still usable after the timeout
+	let code = "std.foldl(function(a, b) a + b, std.range(0, 2000), 0)";
+	let v = s.evaluate_snippet("snip".to_owned(), code)?;
+	ensure_val_eq!(v, Val::Num(2_001_000.into()));
+
+	// Lazy values are manifested as a part of the evaluation which produced them
+	let lazy = format!("{{a: {code}}}");
+	let v = s.evaluate_snippet("snip".to_owned(), lazy.clone())?;
+	std::thread::sleep(Duration::from_millis(100));
+	s.reset_evaluation_limits();
+	ensure_eq!(
+		v.manifest(JsonFormat::default())?,
+		"{\n    \"a\": 2001000\n}"
+	);
+	let v = s.evaluate_snippet("snip".to_owned(), lazy)?;
+	std::thread::sleep(Duration::from_millis(100));
+	ensure!(v.manifest(JsonFormat::default()).is_err());
+
+	Ok(())
+}
+
+#[test]
+fn allocation_budget() -> Result<()> {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::new_cwd_fallback()))
+		.allocation_budget(1000);
+	let s = s.build();
+	let trace_format = CompactFormat::default();
+
+	for code in [
+		"std.repeat('x', 1e12)",
+		"std.repeat('xy', 501)",
+		"std.repeat([1, 2], 501)",
+		"std.makeArray(1001, function(i) i)",
+		"std.join(',', std.makeArray(500, function(i) 'ab'))",
+		"std.join([0], std.makeArray(501, function(i) [i]))",
+	] {
+		let Err(e) = s.evaluate_snippet("snip".to_owned(), code) else {
+			bail!("{code} should exceed the budget");
+		};
+		let e = trace_format.format(&e).unwrap();
+		ensure!(e.starts_with("allocation budget exceeded\n"));
+	}
+
+	for code in [
+		"std.length(std.repeat('x', 1000)) == 1000",
+		"std.length(std.repeat([1, 2], 500)) == 1000",
+		"std.length(std.makeArray(1000, function(i) i)) == 1000",
+		"std.length(std.join(',', std.makeArray(500, function(i) 'a'))) == 999",
+	] {
+		let v = s.evaluate_snippet("snip".to_owned(), code)?;
+		ensure_val_eq!(v, Val::Bool(true));
+	}
+
+	Ok(())
+}
+
+#[test]
+fn error_to_val() -> Result<()> {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::FileName));
+	let s = s.build();
+
+	let Err(e) = s.evaluate_snippet(
+		"snip".to_owned(),
+		"local fail(x) = error 'boom: ' + x;\n{ a: fail('a') }.a",
+	) else {
+		bail!("snippet should fail");
+	};
+	let v = e.to_val(&PathResolver::FileName);
+	let json = v.manifest(JsonFormat::minify(
+		#[cfg(feature = "exp-preserve-order")]
+		false,
+	))?;
+	for desc in [
+		"error statement",
+		"function <fail> call",
+		"field <a> access",
+	] {
+		ensure!(json.contains(&format!("\"description\":\"{desc}\"")));
+	}
+	ensure!(json.contains("\"message\":\"runtime error: boom: a\""));
+	ensure!(json.contains(
+		"\"location\":{\"begin\":{\"column\":6,\"line\":2,\"offset\":41},\"end\":{\"column\":15,\"line\":2,\"offset\":50},\"file\":\"snip\"}"
+	));
+	Ok(())
+}
+
+#[test]
+fn with_context_frame() -> Result<()> {
+	let mut s = State::builder();
+	s.context_initializer(ContextInitializer::new(PathResolver::FileName));
+	let s = s.build();
+	let trace_format = CompactFormat::default();
+
+	let Err(e) = s.evaluate_snippet(
+		"snip".to_owned(),
+		"std.withContext('while rendering deployment foo', error 'boom')",
+	) else {
+		bail!("snippet should fail");
+	};
+	let e = trace_format.format(&e).unwrap();
+	ensure!(e.starts_with("runtime error: boom\n"));
+	let Some(frame) = e
+		.lines()
+		.find(|l| l.ends_with(" while rendering deployment foo"))
+	else {
+		bail!("context frame is missing:\n{e}");
+	};
+	ensure!(frame.trim_start().starts_with("snip:1:1-65:"));
+
+	// Frame only covers the body
+	let Err(e) = s.evaluate_snippet(
+		"snip".to_owned(),
+		"local a = std.withContext('ctx', 1); a + error 'later'",
+	) else {
+		bail!("snippet should fail");
+	};
+	let e = trace_format.format(&e).unwrap();
+	ensure!(!e.contains("ctx"));
+	Ok(())
+}
@@ -0,0 +1,53 @@
+use std::{cell::RefCell, rc::Rc};
+
+use jrsonnet_evaluator::{function::builtin, trace::PathResolver, Result, State, Val};
+use jrsonnet_stdlib::ContextInitializer;
+
+mod common;
+
+#[builtin(fields(
+	calls: Rc<RefCell<usize>>,
+))]
+fn counting_key(this: &counting_key, v: Val) -> Val {
+	*this.calls.borrow_mut() += 1;
+	v
+}
+
+fn counting_state() -> (State, Rc<RefCell<usize>>) {
+	let calls = Rc::new(RefCell::new(0));
+	let mut state = State::builder();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	std.add_native(
+		"key",
+		counting_key {
+			calls: calls.clone(),
+		},
+	);
+	state.context_initializer(std);
+	(state.build(), calls)
+}
+
+#[test]
+fn sort_by_evaluates_key_once() -> Result<()> {
+	let (state, calls) = counting_state();
+	let v = state.evaluate_snippet(
+		"test",
+		"std.sortBy(std.reverse(std.range(1, 1000)), std.native('key'))",
+	)?;
+	ensure_eq!(v.as_arr().expect("array").len(), 1000);
+	ensure_eq!(*calls.borrow(), 1000);
+	Ok(())
+}
+
+#[test]
+fn min_max_by_evaluate_key_once() -> Result<()> {
+	let (state, calls) = counting_state();
+	let v = state.evaluate_snippet("test", "std.minBy(std.range(1, 1000), std.native('key'))")?;
+	ensure_eq!(v.as_num(), Some(1.0));
+	ensure_eq!(*calls.borrow(), 1000);
+
+	let v = state.evaluate_snippet("test", "std.maxBy(std.range(1, 1000), std.native('key'))")?;
+	ensure_eq!(v.as_num(), Some(1000.0));
+	ensure_eq!(*calls.borrow(), 2000);
+	Ok(())
+}
@@ -1,6 +1,8 @@
-use jrsonnet_evaluator::{function::builtin, trace::PathResolver, State};
+use jrsonnet_evaluator::{bail, function::builtin, trace::PathResolver, Result, State};
 use jrsonnet_stdlib::ContextInitializer;
 
+mod common;
+
 #[builtin]
 fn example_native(a: u32, b: u32) -> u32 {
 	a + b
@@ -20,3 +22,27 @@ fn std_native() {
 		.as_bool()
 		.expect("boolean output"));
 }
+
+#[test]
+fn strict_native() -> Result<()> {
+	let mut state = State::builder();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	std.add_native("example", example_native::INST);
+	state.context_initializer(std.clone());
+	let state = state.build();
+
+	let v = state.evaluate_snippet("test", "std.native('typo') == null")?;
+	ensure_eq!(v.as_bool(), Some(true));
+
+	std.settings_mut().strict_native = true;
+	let v = state.evaluate_snippet("test", "std.native('example')(1, 3)")?;
+	ensure_eq!(v.as_num(), Some(4.0));
+	let Err(e) = state.evaluate_snippet("test", "std.native('typo')") else {
+		bail!("unknown native should fail in strict mode");
+	};
+	ensure_eq!(
+		e.error().to_string(),
+		"runtime error: unknown native function 'typo'"
+	);
+	Ok(())
+}
@@ -45,6 +45,11 @@ fn test() -> io::Result<()> {
 		if !entry.path().extension().map_or(false, |e| e == "jsonnet") {
 			continue;
 		}
+		// Many functions have an additional `preserve_order` parameter with this feature
+		#[cfg(feature = "exp-preserve-order")]
+		if entry.file_name() == "std_param_names.jsonnet" {
+			continue;
+		}
 
 		run(&entry.path());
 	}
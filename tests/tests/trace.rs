@@ -0,0 +1,52 @@
+use jrsonnet_evaluator::{trace::PathResolver, Result, State};
+use jrsonnet_stdlib::{CollectingTracePrinter, ContextInitializer};
+
+mod common;
+
+#[test]
+fn collect_traces() -> Result<()> {
+	let traces = CollectingTracePrinter::default();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	std.settings_mut().trace_printer = Box::new(traces.clone());
+	let mut state = State::builder();
+	state.context_initializer(std);
+	let state = state.build();
+
+	let v = state.evaluate_snippet(
+		"snip",
+		"local a = std.trace('first', 1);\nstd.trace({b: [a]}, a + 1)",
+	)?;
+	ensure_eq!(v.as_num(), Some(2.0));
+
+	let collected = traces.take();
+	ensure_eq!(collected.len(), 2);
+	// Local is forced by manifestification of the outer trace message
+	ensure_eq!(&*collected[0].message, "first");
+	ensure_eq!(&*collected[1].message, "{\n   \"b\": [\n      1\n   ]\n}");
+	let Some(location) = &collected[0].location else {
+		jrsonnet_evaluator::bail!("trace location should be known");
+	};
+	ensure_eq!(location.0.source_path().to_string(), "snip");
+	ensure!(traces.traces().is_empty());
+	Ok(())
+}
+
+#[test]
+fn trace_with_location() -> Result<()> {
+	let traces = CollectingTracePrinter::default();
+	let std = ContextInitializer::new(PathResolver::Absolute);
+	{
+		let mut settings = std.settings_mut();
+		settings.trace_printer = Box::new(traces.clone());
+		settings.trace_with_location = true;
+	}
+	let mut state = State::builder();
+	state.context_initializer(std);
+	let state = state.build();
+
+	state.evaluate_snippet("snip", "local a = 1;\n  std.trace('here', a)")?;
+	let collected = traces.take();
+	ensure_eq!(collected.len(), 1);
+	ensure_eq!(&*collected[0].message, "snip:2:3: here");
+	Ok(())
+}